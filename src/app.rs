@@ -1,9 +1,23 @@
 // src/app.rs
 
-use chrono::{Duration as ChronoDuration, Local, NaiveDateTime, NaiveTime};
-use crossterm::event::{Event as CrosstermEvent, KeyCode};
-use std::collections::VecDeque;
-use std::path::PathBuf;
+use chrono::{DateTime, Datelike, Days, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use crossterm::event::{Event as CrosstermEvent, KeyCode, MouseEventKind};
+use arboard::Clipboard;
+use ratatui::layout::Rect;
+use reqwest::Method;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::{parse_cron, Config, TimeConfig};
+use cron::Schedule;
+use crate::rate_limit::TokenBucket;
+use crate::state::{ClockState, SavedStats};
+use crate::data::{DataManager, DATE_DIR_FORMAT};
+use serde::{Deserialize, Serialize};
 
 // アプリケーションモードの列挙型
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -12,6 +26,111 @@ pub enum AppMode {
     ClockMode,  // クロックモード
 }
 
+// ログのレベル (ログの1行ごとに持ち、min_log_level より低いものは出力しない)
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug, // デバッグ用の詳細ログ
+    #[default]
+    Info,
+    Warn,  // 動作は続けるが、見落とすべきでないもの
+    Error,
+}
+
+impl LogLevel {
+    // ログの本文の前に付ける印 (Info は付けない)
+    fn label(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG: ",
+            LogLevel::Info => "",
+            LogLevel::Warn => "WARN: ",
+            LogLevel::Error => "ERROR: ",
+        }
+    }
+}
+
+// ログ履歴の1行
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub timestamp: String, // HH:MM:SS
+    pub message: String,
+    pub is_status: bool,   // ステータス欄にも表示したメッセージ (ログ表示で色を変える)
+}
+
+impl LogRecord {
+    // 表示用の1行 (ログファイルにもこのまま書き込む)
+    pub fn text(&self) -> String {
+        format!("{}: {}{}", self.timestamp, self.level.label(), self.message)
+    }
+}
+
+// 失敗したAPI呼び出しの情報 (再試行用)
+#[derive(Debug, Clone)]
+pub struct FailedRequest {
+    pub endpoint: String,
+    pub method: Method,
+    pub is_first_call: bool, // 初回呼び出しが失敗した場合は、再試行も初回として扱う
+}
+
+// キー入力の結果、main 側で実行が必要な操作
+#[derive(Debug)]
+pub enum AppAction {
+    RetryFailed(FailedRequest), // 前回失敗した呼び出しを再試行する
+    TriggerNow(Vec<TriggerTarget>), // スケジュールを待たずにすべてのエンドポイントを呼び出す
+    ReloadConfig, // 設定ファイルを読み込み直して適用する
+}
+
+// 最近保存したファイルの記録 (保存ファイル一覧ペイン用)
+#[derive(Debug, Clone)]
+pub struct SaveRecord {
+    pub filename: String, // 保存したファイル名
+    pub bytes: u64,       // ファイルサイズ
+    pub status: u16,      // HTTPステータスコード
+    pub ts: String,       // 保存時刻 (HH:MM:SS)
+}
+
+// 条件付きリクエストに使う、前回保存したレスポンスの ETag / Last-Modified
+#[derive(Debug, Clone, Default)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+// ステータス欄に表示する接続状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Unknown, // まだ呼び出していない
+    Healthy, // 直近の呼び出しが成功し、次の実行予定を過ぎていない
+    Stale,   // 直近の呼び出しは成功したが、その後の実行予定を過ぎても成功していない
+    Failing, // 直近の呼び出しが失敗した
+}
+
+// トリガー時に呼び出すエンドポイントとその保存先
+pub type TriggerTarget = (String, Option<PathBuf>);
+
+// 失敗のデスクトップ通知を出す最短の間隔 (失敗が続くエンドポイントで通知が溢れないように)
+const ERROR_NOTIFICATION_INTERVAL: Duration = Duration::from_secs(60);
+
+// 壁時計が単調増加する時計とこの秒数以上ずれたら、システム時刻が変更されたとみなす
+const CLOCK_ADJUSTMENT_THRESHOLD_SECS: i64 = 5;
+
+// 実行予定の時刻から呼び出しが成功するまでに見込む猶予 (再試行やタイムアウトを待つ時間)
+const HEALTH_GRACE_SECS: i64 = 60;
+
+// 保存ファイル一覧ペインに保持する件数
+pub const MAX_RECENT_SAVES: usize = 8;
+
+// 結果の履歴パネルに表示する件数
+pub const MAX_CALL_HISTORY: usize = 5;
+
+// 終了コード: 正常終了 (最後の呼び出しが成功、またはまだ呼び出していない)
+pub const EXIT_CODE_OK: u8 = 0;
+// 終了コード: 最後のAPI呼び出しが失敗した
+pub const EXIT_CODE_CALL_FAILED: u8 = 1;
+// 終了コード: 設定の読み込みや起動時のセットアップに失敗した
+pub const EXIT_CODE_SETUP_FAILED: u8 = 2;
+
 // アプリケーションの状態を管理する構造体
 pub struct App {
     pub current_time: String,
@@ -22,32 +141,96 @@ pub struct App {
     pub initial_s: u32,
     pub error_message: Option<String>,
     pub status_message: Option<String>,
-    pub api_endpoint: String, // 追加: APIエンドポイント
+    pub api_endpoints: Vec<String>, // 追加: APIエンドポイント (トリガーのたびにすべて呼び出す)
 
     // 定刻モード用
     pub next_trigger_time: Option<NaiveDateTime>,
+    pub trigger_times: Vec<NaiveTime>, // 定刻モードで複数の実行時刻を指定した場合の一覧 (空なら initial_h/m/s のみ)
+    pub cron_schedule: Option<Schedule>, // cron を指定した場合の実行スケジュール (trigger_times より優先)
+
+    // レスポンスのレート制限ヘッダー (Retry-After 等) により次回実行を延期する期限
+    pub rate_limit_until: Option<NaiveDateTime>,
+    pub rate_limited_count: u64, // 429 (Too Many Requests) を受け取った回数
+
+    // API呼び出しの統計
+    pub calls_total: u64,                       // 送信したAPI呼び出しの回数
+    pub calls_ok: u64,                          // 成功した回数
+    pub calls_failed: u64,                      // 失敗した回数
+    pub last_success_at: Option<NaiveDateTime>, // 最後に成功した時刻
+    pub last_call_ok: Option<bool>, // 最後の呼び出しが成功したかどうか (まだ呼び出していなければ None)
+    pub last_call_at: Option<DateTime<Local>>, // 最後に呼び出した時刻 (成功・失敗を問わない。メトリクス用)
+    pub last_error_notified_at: Option<Instant>, // 最後に失敗をデスクトップ通知した時刻
+    pub completed_fetches: u64, // 完了した初回以外の呼び出しの回数 (max_calls の判定用)
+    pub setup_failed: bool, // 設定の読み込みや起動時のセットアップに失敗したかどうか (終了コードの判定用)
+
+    // 複数のエンドポイントを同時に呼び出した場合の集計 (直近のトリガー分)
+    pub batch_total: usize, // 呼び出したエンドポイントの数
+    pub batch_done: usize,  // 完了した数
+    pub batch_ok: usize,    // 成功した数
 
     // クロックモード用
     pub total_duration: ChronoDuration, // 設定されたタイマーの総時間
     pub remaining_duration: ChronoDuration, // 残り時間
 
     // ログ機能
-    pub logs: VecDeque<String>, // ログ履歴を保持 (最大256個)
+    pub logs: VecDeque<LogRecord>, // ログ履歴を保持 (最大 max_logs 個)
     pub log_scroll: usize,      // ログのスクロール位置 (表示されるログの先頭行のインデックス)
     pub max_logs: usize,        // ログの最大保持数
     pub is_log_auto_scroll: bool, // ログが自動スクロールモードかどうか
+    pub filter_query: Option<String>, // ログの絞り込み文字列 ('/' で入力、Esc で解除。大文字小文字は区別しない)
+    pub is_filter_editing: bool,      // 絞り込み文字列を入力中かどうか
+    pub log_file: Option<File>,   // ログの追記先 (log_file が設定されている場合のみ)
+    pub log_view_height: usize, // ログ表示領域の実際の行数 (描画時に ui が更新する)
 
     // 新規追加
+    pub paused: bool, // 一時停止中はタイマーを進めず、スケジュールによる呼び出しも行わない ('p' キーで切り替え)
     pub is_first_api_call: bool, // API呼び出しが初回かどうかを判断するフラグ
+    pub startup_countdown: Option<u64>, // startup_delay_secs により初回の呼び出しを待っている間の残り秒数
     pub today_json_dir: Option<PathBuf>, // 今日のJSON保存ディレクトリのパス
+    pub endpoint_json_dirs: Vec<PathBuf>, // エンドポイントごとの保存先 (複数のエンドポイントを呼び出す場合のみ)
+    pub last_rollover_date: Option<NaiveDate>, // 日付の変更を最後に処理した日付 (ディレクトリの作成に失敗しても毎秒やり直さないように)
+    pub config: Arc<Config>, // 読み込んだ設定 (API呼び出しのオプション参照用)
+    pub rate_limiter: Option<TokenBucket>, // 全リクエストで共有するレート制限 (未設定ならNone)
+    pub last_request_at: HashMap<String, Instant>, // エンドポイントごとに最後にリクエストを送信した時刻 (min_interval_secs の判定用)
+    pub last_manual_trigger_at: Option<Instant>, // 最後に手動で実行した時刻 (manual_cooldown_secs の判定用)
+
+    // 直近で失敗したAPI呼び出し ('R' キーで再試行)
+    pub last_failed_request: Option<FailedRequest>,
+    pub last_fired_trigger: Option<NaiveDateTime>, // 定刻モードで最後に実行した実行時刻 (時刻が巻き戻った場合の二重実行防止)
+    pub last_tick: Option<(Instant, NaiveDateTime)>, // 前回の tick の時刻 (システム時刻の変更の検出用)
+    pub cache_validators: HashMap<String, CacheValidators>, // エンドポイントごとの前回の ETag / Last-Modified (実行中のみ保持)
+    pub last_body_hashes: HashMap<String, u64>, // エンドポイントごとに最後に保存した本文のハッシュ (skip_unchanged 用。実行中のみ保持)
+    pub status_counts: HashMap<u16, u64>,       // 受け取ったHTTPステータスコードごとの回数 (ステータス集計パネル用。実行中のみ保持)
+
+    pub is_quit_confirming: bool, // 'q' を押して終了の確認 (y/n) を表示している
+
+    // 時刻・間隔の編集フォーム ('i' キーで開く。編集中は入力中の文字列を保持)
+    pub time_input: Option<String>,
+
+    // 保存ファイル一覧ペイン
+    pub recent_saves: VecDeque<SaveRecord>, // 最近保存したファイル (最大 MAX_RECENT_SAVES 件)
+    pub show_recent_saves: bool,            // ペインを表示するかどうか ('l' キーで切り替え)
+    pub is_compact_ui: bool,                // コンパクト表示 (上部の情報を1行にまとめる。'v' キーで切り替え)
+    pub last_saved_path: Option<PathBuf>,   // 最後に保存したファイルのパス ('y' キーでクリップボードにコピー)
+    pub clipboard: Option<Clipboard>,       // 'y' キーで初めて使うときに作成する (Linux では保持している間だけ貼り付けられるため残しておく)
+    pub call_history: VecDeque<(String, String)>, // 最近のAPI呼び出しの結果 (時刻, ステータスコードまたはエラー)
+
+    // クロックモードの残り時間の保存先 (データディレクトリのセットアップ後に設定)
+    pub clock_state_path: Option<PathBuf>,
+    // 呼び出しの累計の保存先 (stats_file を設定した場合に起動時に設定し、終了時に書き出す)
+    pub stats_path: Option<PathBuf>,
+    // ログを stderr にも出力する (--headless で画面を使わない場合)
+    pub log_to_stderr: bool,
 }
 
 impl App {
     // APIエンドポイントを引数に追加
-    pub fn new(mode: AppMode, h: u32, m: u32, s: u32, api_endpoint: String) -> App {
-        let total_duration = ChronoDuration::hours(h as i64)
-            + ChronoDuration::minutes(m as i64)
-            + ChronoDuration::seconds(s as i64);
+    pub fn new(mode: AppMode, h: u32, m: u32, s: u32, api_endpoints: Vec<String>, config: Arc<Config>) -> App {
+        let rate_limiter = config.rate_limit.as_ref()
+            .map(|rate_limit| TokenBucket::new(rate_limit.capacity, rate_limit.refill_per_sec));
+        let total_duration = Self::duration_from_hms(h, m, s);
+        let max_logs = config.max_logs;
+        let is_compact_ui = config.compact_ui;
         App {
             current_time: String::new(),
             running: true,
@@ -57,47 +240,129 @@ impl App {
             initial_s: s,
             error_message: None,
             status_message: None,
-            api_endpoint, // ここで設定
+            api_endpoints, // ここで設定
             next_trigger_time: None,
+            trigger_times: config.times_of_day(),
+            cron_schedule: Self::cron_schedule_for(&config),
+            rate_limit_until: None,
+            rate_limited_count: 0,
+            calls_total: 0,
+            last_call_ok: None,
+            completed_fetches: 0,
+            setup_failed: false,
+            calls_ok: 0,
+            calls_failed: 0,
+            last_success_at: None,
+            last_call_at: None,
+            last_error_notified_at: None,
+            batch_total: 0,
+            batch_done: 0,
+            batch_ok: 0,
             total_duration,
             remaining_duration: total_duration,
-            logs: VecDeque::with_capacity(256), // 容量を事前に確保
+            logs: VecDeque::with_capacity(max_logs), // 容量を事前に確保
             log_scroll: 0, // 初期スクロール位置は最上部
-            max_logs: 256,
+            max_logs,
             is_log_auto_scroll: true, // 初期状態では自動スクロールを有効にする
+            log_view_height: 0,
+            filter_query: None,
+            is_filter_editing: false,
+            log_file: None,
+            paused: false,
             is_first_api_call: true, // 初期値はtrue
+            startup_countdown: None,
             today_json_dir: None,    // 初期値はNone
+            endpoint_json_dirs: Vec::new(),
+            last_rollover_date: None,
+            config,
+            rate_limiter,
+            last_failed_request: None,
+            last_fired_trigger: None,
+            last_tick: None,
+            cache_validators: HashMap::new(),
+            last_body_hashes: HashMap::new(),
+            status_counts: HashMap::new(),
+            last_request_at: HashMap::new(),
+            last_manual_trigger_at: None,
+            is_quit_confirming: false,
+            time_input: None,
+            recent_saves: VecDeque::with_capacity(MAX_RECENT_SAVES),
+            show_recent_saves: false,
+            is_compact_ui,
+            last_saved_path: None,
+            clipboard: None,
+            call_history: VecDeque::with_capacity(MAX_CALL_HISTORY),
+            clock_state_path: None,
+            stats_path: None,
+            log_to_stderr: false,
         }
     }
     
+    // 秒数が ChronoDuration の範囲を超える場合は最大値に飽和させる (パニックさせない)
+    fn duration_from_hms(h: u32, m: u32, s: u32) -> ChronoDuration {
+        let total_seconds = TimeConfig { h, m, s }.total_seconds();
+        i64::try_from(total_seconds).ok()
+            .and_then(ChronoDuration::try_seconds)
+            .unwrap_or(ChronoDuration::MAX)
+    }
+
     pub fn update_time(&mut self) {
         // ここが修正箇所： %M はゼロパディングされた分、%S はゼロパディングされた秒
         // 確認のため、日本語の「分」と「秒」の文字を明示的に追加しています。
         self.current_time = Local::now().format("%Y年%m月%d日 %H時%M分%S秒").to_string();
     }
 
-    pub fn handle_event(&mut self, event: &CrosstermEvent, log_display_height: u16) {
+    // キー入力を処理する。main 側での処理が必要な操作があれば AppAction を返す
+    pub fn handle_event(&mut self, event: &CrosstermEvent) -> Option<AppAction> {
+        let mut action = None;
+        // マウスホイールは矢印キーと同じく1行ずつスクロールする
+        if let CrosstermEvent::Mouse(mouse) = event {
+            match mouse.kind {
+                MouseEventKind::ScrollUp => self.scroll_log_up(1),
+                MouseEventKind::ScrollDown => self.scroll_log_down(1),
+                _ => {}
+            }
+        }
+        // 端末のサイズが変わったら、次の描画を待たずにログの表示行数とスクロール位置を合わせる
+        if let CrosstermEvent::Resize(width, height) = event {
+            let height = crate::ui::log_view_height(self, Rect::new(0, 0, *width, *height));
+            self.set_log_view_height(height);
+        }
         if let CrosstermEvent::Key(key) = event {
-            // スクロール可能な最大位置を計算
-            let max_scroll_position = self.logs.len().saturating_sub(log_display_height as usize).max(0);
+            // 終了の確認中は y で終了、n / Esc で取り消す (それ以外のキーは無視する)
+            if self.is_quit_confirming {
+                match key.code {
+                    KeyCode::Char('y') => self.running = false,
+                    KeyCode::Char('n') | KeyCode::Esc => self.is_quit_confirming = false,
+                    _ => {}
+                }
+                return None;
+            }
+            // 編集フォームが開いている間は、キー入力をすべてフォームで処理する
+            if self.time_input.is_some() {
+                self.handle_time_input(key.code);
+                return None;
+            }
+            // 絞り込みの入力中も同様
+            if self.is_filter_editing {
+                self.handle_filter_input(key.code);
+                return None;
+            }
+
+            // スクロール可能な最大位置を計算 (ui が記録した実際の表示行数と、絞り込み後の行数を使う)
+            let max_scroll_position = self.visible_logs().len().saturating_sub(self.log_view_height);
 
             match key.code {
+                KeyCode::Char('q') if self.config.confirm_quit => {
+                    self.is_quit_confirming = true;
+                }
                 KeyCode::Char('q') => {
                     self.running = false;
                 }
-                KeyCode::Up => {
-                    self.log_scroll = self.log_scroll.saturating_sub(1);
-                    self.is_log_auto_scroll = false;
-                }
-                KeyCode::Down => {
-                    self.log_scroll = self.log_scroll.saturating_add(1);
-                    if self.log_scroll >= max_scroll_position {
-                        self.is_log_auto_scroll = true;
-                        self.log_scroll = max_scroll_position;
-                    } else {
-                        self.is_log_auto_scroll = false;
-                    }
-                }
+                KeyCode::Up => self.scroll_log_up(1),
+                KeyCode::Down => self.scroll_log_down(1),
+                KeyCode::PageUp => self.scroll_log_up(self.log_view_height.max(1)),
+                KeyCode::PageDown => self.scroll_log_down(self.log_view_height.max(1)),
                 KeyCode::Home => {
                     self.log_scroll = 0;
                     self.is_log_auto_scroll = false;
@@ -106,64 +371,1191 @@ impl App {
                     self.log_scroll = max_scroll_position;
                     self.is_log_auto_scroll = true;
                 }
+                KeyCode::Char('l') => {
+                    self.show_recent_saves = !self.show_recent_saves;
+                }
+                KeyCode::Char('v') => {
+                    self.is_compact_ui = !self.is_compact_ui;
+                }
+                KeyCode::Char('y') => self.copy_last_saved_path(),
+                KeyCode::Char('c') => self.clear_logs(),
+                KeyCode::Char('e') => self.export_logs(Local::now()),
+                KeyCode::Char('/') => {
+                    self.filter_query = Some(String::new());
+                    self.is_filter_editing = true;
+                    self.is_log_auto_scroll = true;
+                }
+                KeyCode::Esc if self.filter_query.is_some() => {
+                    self.clear_filter();
+                }
+                KeyCode::Char('i') => {
+                    // cron で指定したスケジュールは時:分:秒では編集できない
+                    if self.mode == AppMode::OnTimeMode && self.cron_schedule.is_some() {
+                        self.set_error("cron で実行スケジュールを指定しているため、実行時刻は編集できません。".to_string());
+                    } else {
+                        self.time_input = Some(format!("{}:{}:{}", self.initial_h, self.initial_m, self.initial_s));
+                    }
+                }
+                KeyCode::Char('r') => {
+                    // タイマーや次回の実行時刻はそのままにして、今すぐ呼び出す
+                    if self.api_endpoints.is_empty() || self.today_json_dir.is_none() {
+                        self.set_error("設定またはデータディレクトリが未準備のため、手動で実行できません。".to_string());
+                    } else if self.accept_manual_trigger(Instant::now()) {
+                        self.set_status_message(format!("手動トリガー: {}件のエンドポイントを今すぐ呼び出します。", self.api_endpoints.len()));
+                        action = Some(AppAction::TriggerNow(self.trigger_targets()));
+                    }
+                }
+                KeyCode::Char('p') => {
+                    self.toggle_pause();
+                }
+                KeyCode::Char('m') => {
+                    self.toggle_mode();
+                }
+                KeyCode::F(5) => {
+                    if self.today_json_dir.is_none() {
+                        self.set_error("データディレクトリが未準備のため、設定を再読み込みできません。アプリケーションを再起動してください。".to_string());
+                    } else {
+                        action = Some(AppAction::ReloadConfig);
+                    }
+                }
+                KeyCode::Char('R') => {
+                    match self.last_failed_request.clone() {
+                        Some(_) if !self.accept_manual_trigger(Instant::now()) => {}
+                        Some(request) => {
+                            self.set_status_message(format!("前回失敗した呼び出しを再試行: {} {}", request.method, request.endpoint));
+                            action = Some(AppAction::RetryFailed(request));
+                        }
+                        None => self.set_status_message("再試行する失敗した呼び出しはありません。".to_string()),
+                    }
+                }
                 _ => {
-                    // その他のキー入力は無視（モード切り替えキーは削除）
+                    // その他のキー入力は無視
                 }
             }
             self.log_scroll = self.log_scroll.min(max_scroll_position);
         }
+        action
+    }
+
+    // ログを上にスクロールする (自動スクロールを止める)
+    fn scroll_log_up(&mut self, lines: usize) {
+        self.log_scroll = self.log_scroll.saturating_sub(lines);
+        self.is_log_auto_scroll = false;
+    }
+
+    // ログを下にスクロールする (一番下まで到達したら自動スクロールに戻す)
+    fn scroll_log_down(&mut self, lines: usize) {
+        // スクロール可能な最大位置 (ui が記録した実際の表示行数と、絞り込み後の行数を使う)
+        let max_scroll_position = self.visible_logs().len().saturating_sub(self.log_view_height);
+        self.log_scroll = self.log_scroll.saturating_add(lines);
+        if self.log_scroll >= max_scroll_position {
+            self.is_log_auto_scroll = true;
+            self.log_scroll = max_scroll_position;
+        } else {
+            self.is_log_auto_scroll = false;
+        }
+    }
+
+    // 編集フォームのキー入力 (数字と ':' を入力、Enter で適用、Esc で取り消し)
+    fn handle_time_input(&mut self, code: KeyCode) {
+        let Some(input) = self.time_input.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c) if c.is_ascii_digit() || c == ':' => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.time_input = None,
+            KeyCode::Enter => {
+                let input = self.time_input.take().unwrap_or_default();
+                if let Err(e) = self.apply_time_input(&input) {
+                    self.set_error(format!("時刻・間隔の変更に失敗しました: {}", e));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 絞り込みのキー入力 (文字を入力、Enter で確定、Esc で解除)
+    fn handle_filter_input(&mut self, code: KeyCode) {
+        let Some(query) = self.filter_query.as_mut() else {
+            self.is_filter_editing = false;
+            return;
+        };
+        match code {
+            KeyCode::Char(c) => query.push(c),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Esc => self.clear_filter(),
+            KeyCode::Enter => {
+                self.is_filter_editing = false;
+                // 空のまま確定した場合は絞り込まない
+                if self.filter_query.as_deref() == Some("") {
+                    self.filter_query = None;
+                }
+            }
+            _ => {}
+        }
+        // 絞り込み条件が変わったら、最新のログが見えるようにする
+        self.is_log_auto_scroll = true;
+    }
+
+    fn clear_filter(&mut self) {
+        self.filter_query = None;
+        self.is_filter_editing = false;
+        self.is_log_auto_scroll = true;
+    }
+
+    // ログパネルに表示するログ (絞り込み中は文字列を含む行だけ)
+    pub fn visible_logs(&self) -> Vec<&LogRecord> {
+        match self.filter_query.as_deref() {
+            Some(query) if !query.is_empty() => {
+                let query = query.to_lowercase();
+                self.logs.iter().filter(|record| record.text().to_lowercase().contains(&query)).collect()
+            }
+            _ => self.logs.iter().collect(),
+        }
+    }
+
+    // "時:分:秒" を解析し、設定ファイルと同じルールで検証してから適用する
+    fn apply_time_input(&mut self, input: &str) -> anyhow::Result<()> {
+        let parts: Vec<&str> = input.split(':').collect();
+        let [h, m, s] = parts.as_slice() else {
+            return Err(anyhow::anyhow!("'時:分:秒' の形式で入力してください (入力: {})", input));
+        };
+        let parse = |part: &str| part.parse::<u32>().map_err(|_| anyhow::anyhow!("数値として解釈できません: '{}'", part));
+        let time = TimeConfig { h: parse(h)?, m: parse(m)?, s: parse(s)? };
+        time.validate(self.mode == AppMode::OnTimeMode)?;
+
+        self.initial_h = time.h;
+        self.initial_m = time.m;
+        self.initial_s = time.s;
+        self.total_duration = Self::duration_from_hms(time.h, time.m, time.s);
+
+        match self.mode {
+            AppMode::OnTimeMode => {
+                self.trigger_times.clear(); // 編集した場合は1つの実行時刻に置き換える
+                self.set_next_trigger_time();
+                self.set_status_message(format!("実行時刻を{:02}時{:02}分{:02}秒に変更しました。", time.h, time.m, time.s));
+            }
+            AppMode::ClockMode => {
+                self.reset_timer();
+                self.set_status_message(format!("実行間隔を{}時間{}分{}秒に変更しました。", time.h, time.m, time.s));
+            }
+        }
+        Ok(())
     }
 
     pub fn set_error(&mut self, message: String) {
-        let timestamp = Local::now().format("%H:%M:%S").to_string();
-        let log_entry = format!("{}: ERROR: {}", timestamp, message);
-        self.add_log(log_entry);
+        self.push_record(LogLevel::Error, message.clone(), true);
 
         self.error_message = Some(message);
         self.status_message = None;
     }
 
     pub fn set_status_message(&mut self, message: String) {
-        let timestamp = Local::now().format("%H:%M:%S").to_string();
-        let log_entry = format!("{}: {}", timestamp, message);
-        self.add_log(log_entry);
+        self.push_record(LogLevel::Info, message.clone(), true);
 
         self.status_message = Some(message);
         self.error_message = None;
     }
 
-    pub fn add_log(&mut self, log_entry: String) {
-        if self.logs.len() == self.max_logs {
-            self.logs.pop_front();
+    // 一時停止と再開を切り替える
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if self.paused {
+            self.set_status_message("一時停止しました。".to_string());
+            return;
+        }
+
+        // 一時停止中に定刻を過ぎていた場合は、再開直後に実行せず次の定刻を待つ
+        if self.mode == AppMode::OnTimeMode
+            && self.next_trigger_time.is_some_and(|next_trigger| next_trigger <= Local::now().naive_local())
+        {
+            self.set_next_trigger_time();
+        }
+        self.set_status_message("再開しました。".to_string());
+    }
+
+    // 読み込み直した設定を適用する (F5 キー)。エンドポイントが変わった場合は true を返す
+    // 保存先 (output_dir, run_name) やログファイルなど、起動時にだけ使う設定は再起動するまで変わらない
+    pub fn apply_config(&mut self, config: Config) -> bool {
+        let api_endpoints = config.api.as_slice().to_vec();
+        let endpoints_changed = api_endpoints != self.api_endpoints;
+        if endpoints_changed {
+            self.api_endpoints = api_endpoints;
+            self.endpoint_json_dirs.clear(); // 新しいディレクトリが用意されるまでは日付ディレクトリに保存する
+        }
+
+        let time = config.primary_time();
+        self.initial_h = time.h;
+        self.initial_m = time.m;
+        self.initial_s = time.s;
+        self.trigger_times = config.times_of_day();
+        self.cron_schedule = Self::cron_schedule_for(&config);
+        self.total_duration = Self::duration_from_hms(time.h, time.m, time.s);
+        self.rate_limiter = config.rate_limit.as_ref()
+            .map(|rate_limit| TokenBucket::new(rate_limit.capacity, rate_limit.refill_per_sec));
+        self.mode = if config.on_time { AppMode::OnTimeMode } else { AppMode::ClockMode };
+        self.max_logs = config.max_logs;
+        self.trim_logs(self.max_logs);
+        self.config = Arc::new(config);
+
+        match self.mode {
+            AppMode::OnTimeMode => self.set_next_trigger_time(),
+            AppMode::ClockMode => self.reset_timer(),
+        }
+        self.set_status_message("設定ファイルを再読み込みしました。".to_string());
+        endpoints_changed
+    }
+
+    // 定刻モードとクロックモードを切り替える ('m' キー)
+    // 現在の時刻設定が切り替え先のモードで使えない場合 (例: 定刻モードで25時) は切り替えない
+    pub fn toggle_mode(&mut self) {
+        let next_mode = match self.mode {
+            AppMode::OnTimeMode => AppMode::ClockMode,
+            AppMode::ClockMode => AppMode::OnTimeMode,
+        };
+        // 複数の実行時刻が設定されている場合は、各時刻が読み込み時に検証済み
+        if next_mode == AppMode::ClockMode || self.trigger_times.is_empty() {
+            let time = TimeConfig { h: self.initial_h, m: self.initial_m, s: self.initial_s };
+            if let Err(e) = time.validate(next_mode == AppMode::OnTimeMode) {
+                self.set_error(format!("モードを切り替えられません: {}", e));
+                return;
+            }
+        }
+
+        self.mode = next_mode;
+        match self.mode {
+            AppMode::OnTimeMode => {
+                self.set_next_trigger_time();
+                self.set_status_message("定刻モードに切り替えました。".to_string());
+            }
+            AppMode::ClockMode => {
+                self.reset_timer();
+                self.set_status_message("クロックモードに切り替えました。".to_string());
+            }
+        }
+    }
+
+    // ログの追記先のファイルを開く (起動時に1度だけ呼ぶ)
+    pub fn open_log_file(&mut self, path: &Path) -> std::io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.log_file = Some(file);
+        Ok(())
+    }
+
+    // min_log_level が debug の場合のみログに追加する
+    pub fn add_debug_log(&mut self, message: String) {
+        self.add_log_with_level(LogLevel::Debug, message);
+    }
+
+    // ステータス欄を変えずにログだけに追加する (Info レベル)
+    pub fn add_log(&mut self, message: String) {
+        self.add_log_with_level(LogLevel::Info, message);
+    }
+
+    // レベルを指定してログに追加する (時刻はここで付ける)
+    pub fn add_log_with_level(&mut self, level: LogLevel, message: String) {
+        self.push_record(level, message, false);
+    }
+
+    fn push_record(&mut self, level: LogLevel, message: String, is_status: bool) {
+        // min_log_level より低いレベルのログは、画面にもファイルにも出力しない
+        if level < self.config.min_log_level {
+            return;
+        }
+        let record = LogRecord { level, timestamp: Local::now().format("%H:%M:%S").to_string(), message, is_status };
+
+        // ファイルへの書き込みに失敗してもアプリは止めず、以降のファイル出力だけを止める
+        if let Some(file) = self.log_file.as_mut()
+            && let Err(e) = writeln!(file, "{}", record.text())
+        {
+            self.log_file = None;
+            self.push_log(LogRecord {
+                level: LogLevel::Error,
+                timestamp: record.timestamp.clone(),
+                message: format!("ログファイルへの書き込みに失敗したため、ファイル出力を停止しました: {}", e),
+                is_status: false,
+            });
+        }
+        self.push_log(record);
+    }
+
+    // ログ履歴に追加する (最大数を超えた分は古いものから捨てる)
+    fn push_log(&mut self, record: LogRecord) {
+        if self.log_to_stderr {
+            eprintln!("{}", record.text());
         }
-        self.logs.push_back(log_entry);
+        self.trim_logs(self.max_logs - 1);
+        self.logs.push_back(record);
 
         if self.is_log_auto_scroll {
             self.log_scroll = self.logs.len();
         }
     }
 
+    // 保存先の日付ディレクトリが today より前の日付なら、新しい日付のディレクトリを作るベースディレクトリを返す
+    // 同じ日付について返すのは1度だけ (作成に失敗した場合も、翌日まではやり直さない)
+    pub fn begin_date_rollover(&mut self, today: NaiveDate) -> Option<PathBuf> {
+        let today_dir = self.today_json_dir.as_ref()?;
+        let dir_date = NaiveDate::parse_from_str(today_dir.file_name()?.to_str()?, DATE_DIR_FORMAT).ok()?;
+        if dir_date >= today || self.last_rollover_date == Some(today) {
+            return None;
+        }
+        self.last_rollover_date = Some(today);
+        today_dir.parent().map(Path::to_path_buf)
+    }
+
+    // 新しい日付のディレクトリに保存先を切り替える
+    pub fn finish_date_rollover(&mut self, today_dir: PathBuf, endpoint_dirs: Vec<PathBuf>) {
+        self.set_status_message(format!("日付が変わりました。保存先を '{}' に切り替えます。", today_dir.display()));
+        self.today_json_dir = Some(today_dir);
+        self.endpoint_json_dirs = endpoint_dirs;
+    }
+
+    // 設定の cron 式を解釈する (式は読み込み時に検証済み。cron を指定していなければ None)
+    fn cron_schedule_for(config: &Config) -> Option<Schedule> {
+        config.cron.as_deref().and_then(|cron| parse_cron(cron).ok())
+    }
+
+    // ログをすべて消し、先頭から自動スクロールで表示し直す ('c' キー。ログファイルには影響しない)
+    fn clear_logs(&mut self) {
+        self.logs.clear();
+        self.is_log_auto_scroll = true;
+        self.add_log("ログをクリアしました".to_string());
+        // 自動スクロールは末尾を指すが、残るのは1行だけなので先頭から表示する
+        self.log_scroll = 0;
+    }
+
+    // 画面のログ (絞り込みに関係なくすべて) を保存先のベースディレクトリの log-YYYYMMDD-HHMMSS.txt に書き出す ('e' キー)
+    fn export_logs(&mut self, now: DateTime<Local>) {
+        let dir = DataManager::base_dir(&self.config.output_dir, self.config.run_name.as_deref());
+        let path = dir.join(format!("log-{}.txt", now.format("%Y%m%d-%H%M%S")));
+        let contents: String = self.logs.iter().map(|record| format!("{}\n", record.text())).collect();
+        let line_count = self.logs.len();
+        match std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(&path, contents)) {
+            Ok(()) => self.add_log(format!("ログ ({}行) を '{}' に書き出しました", line_count, path.display())),
+            Err(e) => self.set_error(format!("ログを '{}' に書き出せませんでした: {}", path.display(), e)),
+        }
+    }
+
+    // 最後に保存したファイルのパスを絶対パスにしてクリップボードにコピーする ('y' キー)
+    fn copy_last_saved_path(&mut self) {
+        let Some(path) = self.last_saved_path.clone() else {
+            self.add_log("保存済みファイルがありません".to_string());
+            return;
+        };
+        let path = std::path::absolute(&path).unwrap_or(path).display().to_string();
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => Ok(clipboard),
+            None => Clipboard::new(),
+        };
+        let result = clipboard.and_then(|clipboard| self.clipboard.insert(clipboard).set_text(path.clone()));
+        match result {
+            Ok(()) => self.set_status_message(format!("保存したファイルのパスをクリップボードにコピーしました: {}", path)),
+            Err(e) => self.set_error(format!("クリップボードにコピーできませんでした: {}", e)),
+        }
+    }
+
+    // ログ表示領域の行数を記録し、スクロール位置を有効な範囲に保つ (自動スクロール中は末尾に合わせる)
+    pub fn set_log_view_height(&mut self, height: usize) {
+        self.log_view_height = height;
+        let max_scroll_position = self.visible_logs().len().saturating_sub(height);
+        if self.is_log_auto_scroll {
+            self.log_scroll = max_scroll_position;
+        } else {
+            self.log_scroll = self.log_scroll.min(max_scroll_position);
+        }
+    }
+
+    // ログ履歴を len 行以下になるまで古いものから捨てる
+    fn trim_logs(&mut self, len: usize) {
+        while self.logs.len() > len {
+            self.logs.pop_front();
+        }
+    }
+
+    pub fn record_failed_request(&mut self, endpoint: &str, is_first_call: bool) {
+        self.last_failed_request = Some(FailedRequest {
+            endpoint: endpoint.to_string(),
+            method: self.config.method.as_reqwest(),
+            is_first_call,
+        });
+    }
+
+    // 前回のリクエストから min_interval_secs が経過していなければ false を返す (手動のトリガーや遅い呼び出しの重なりで
+    // 同じエンドポイントを短い間隔で呼び出さないため)。送信する場合は record_request_sent で時刻を記録する
+    pub fn min_interval_elapsed(&self, endpoint: &str, now: Instant) -> bool {
+        let min_interval = Duration::from_secs(self.config.min_interval_secs);
+        self.last_request_at.get(endpoint)
+            .is_none_or(|last_request_at| now.duration_since(*last_request_at) >= min_interval)
+    }
+
+    pub fn record_request_sent(&mut self, endpoint: &str, now: Instant) {
+        if self.config.min_interval_secs > 0 {
+            self.last_request_at.insert(endpoint.to_string(), now);
+        }
+    }
+
+    // 前回の手動の実行から manual_cooldown_secs が経過していれば時刻を記録して true を返す
+    // (キーの連打で同じ呼び出しが重なって送信されないため)。経過していなければログに残して無視する
+    pub fn accept_manual_trigger(&mut self, now: Instant) -> bool {
+        let cooldown = Duration::from_secs(self.config.manual_cooldown_secs);
+        if let Some(last_manual_trigger_at) = self.last_manual_trigger_at
+            && now.duration_since(last_manual_trigger_at) < cooldown
+        {
+            let remaining = cooldown - now.duration_since(last_manual_trigger_at);
+            self.add_log(format!("手動の実行を無視しました (あと{:.1}秒は再実行できません)", remaining.as_secs_f64()));
+            return false;
+        }
+        self.last_manual_trigger_at = Some(now);
+        true
+    }
+
+    // 同じエンドポイントへの呼び出しが成功したら、再試行対象から外す
+    pub fn clear_failed_request(&mut self, endpoint: &str) {
+        if self.last_failed_request.as_ref().is_some_and(|request| request.endpoint == endpoint) {
+            self.last_failed_request = None;
+        }
+    }
+
+    // エンドポイントの保存先 (エンドポイントごとのディレクトリがなければ今日のディレクトリ)
+    pub fn json_dir_for(&self, endpoint: &str) -> Option<PathBuf> {
+        self.api_endpoints.iter()
+            .position(|candidate| candidate == endpoint)
+            .and_then(|index| self.endpoint_json_dirs.get(index).cloned())
+            .or_else(|| self.today_json_dir.clone())
+    }
+
+    // トリガー時に呼び出すエンドポイントと保存先の一覧を返し、成功数の集計を始める
+    pub fn trigger_targets(&mut self) -> Vec<TriggerTarget> {
+        self.batch_total = self.api_endpoints.len();
+        self.batch_done = 0;
+        self.batch_ok = 0;
+        self.api_endpoints.iter()
+            .map(|endpoint| (endpoint.clone(), self.json_dir_for(endpoint)))
+            .collect()
+    }
+
+    // 失敗をデスクトップ通知してよいか (notify_on_error が有効で、前回の通知から十分に時間が経っている場合のみ)
+    pub fn should_notify_error(&mut self, now: Instant) -> bool {
+        if !self.config.notify_on_error
+            || self.last_error_notified_at.is_some_and(|notified_at| now.duration_since(notified_at) < ERROR_NOTIFICATION_INTERVAL)
+        {
+            return false;
+        }
+        self.last_error_notified_at = Some(now);
+        true
+    }
+
+    // 初回以外の呼び出しが完了したことを記録し、max_calls に達したら終了する (0 または未設定なら無制限)
+    pub fn record_completed_fetch(&mut self) {
+        self.completed_fetches += 1;
+        if let Some(max_calls) = self.config.max_calls
+            && max_calls > 0
+            && self.completed_fetches >= max_calls
+        {
+            self.set_status_message("指定回数に達したため終了します".to_string());
+            self.running = false;
+        }
+    }
+
+    // 以前の実行までの累計を引き継ぐ (stats_file)
+    pub fn restore_stats(&mut self, stats: &SavedStats) {
+        self.calls_total = stats.calls_total;
+        self.calls_ok = stats.calls_ok;
+        self.calls_failed = stats.calls_err;
+        self.last_success_at = stats.last_success_at;
+    }
+
+    // 統計ファイルに書き出す累計
+    pub fn saved_stats(&self) -> SavedStats {
+        SavedStats {
+            calls_total: self.calls_total,
+            calls_ok: self.calls_ok,
+            calls_err: self.calls_failed,
+            last_success_at: self.last_success_at,
+        }
+    }
+
+    // API呼び出しの結果を統計に反映する
+    pub fn record_call_result(&mut self, ok: bool) {
+        self.calls_total += 1;
+        self.last_call_at = Some(Local::now());
+        self.last_call_ok = Some(ok);
+        if ok {
+            self.calls_ok += 1;
+            self.last_success_at = Some(Local::now().naive_local());
+        } else {
+            self.calls_failed += 1;
+        }
+
+        // 複数のエンドポイントをまとめて呼び出した場合は、すべて完了した時点で成功数を表示する
+        if self.batch_total > 1 && self.batch_done < self.batch_total {
+            self.batch_done += 1;
+            if ok {
+                self.batch_ok += 1;
+            }
+            if self.batch_done == self.batch_total {
+                let message = format!("{}/{} 件のエンドポイントの呼び出しが成功しました。", self.batch_ok, self.batch_total);
+                if self.batch_ok == self.batch_total {
+                    self.set_status_message(message);
+                } else {
+                    self.set_error(message);
+                }
+            }
+        }
+    }
+
+    // 次回の実行予定時刻 (定刻モードは next_trigger_time、クロックモードは残り時間から求める)
+    pub fn next_run_at(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self.mode {
+            AppMode::OnTimeMode => self.next_trigger_time,
+            AppMode::ClockMode => now.checked_add_signed(self.remaining_duration),
+        }
+    }
+
+    // ハートビートとして出力する1行の統計
+    pub fn heartbeat_line(&self, now: NaiveDateTime) -> String {
+        let last_ok = match self.last_success_at {
+            Some(at) => format!("{}s ago", (now - at).num_seconds().max(0)),
+            None => "never".to_string(),
+        };
+        let next = match self.next_run_at(now) {
+            Some(at) => at.format("%H:%M:%S").to_string(),
+            None => "-".to_string(),
+        };
+        format!(
+            "[heartbeat] calls={} ok={} fail={} last_ok={} next={}",
+            self.calls_total, self.calls_ok, self.calls_failed, last_ok, next
+        )
+    }
+
+    // 受け取ったステータスコードを集計する
+    pub fn record_status(&mut self, status: u16) {
+        *self.status_counts.entry(status).or_insert(0) += 1;
+    }
+
+    // ステータスコードごとの回数 (多い順。同じ回数ならステータスコードの順)
+    pub fn status_summary(&self) -> Vec<(u16, u64)> {
+        let mut summary: Vec<(u16, u64)> = self.status_counts.iter().map(|(status, count)| (*status, *count)).collect();
+        summary.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        summary
+    }
+
+    pub fn add_call_history(&mut self, outcome: String) {
+        if self.call_history.len() == MAX_CALL_HISTORY {
+            self.call_history.pop_front();
+        }
+        self.call_history.push_back((Local::now().format("%H:%M:%S").to_string(), outcome));
+    }
+
+    pub fn add_recent_save(&mut self, record: SaveRecord) {
+        if self.recent_saves.len() == MAX_RECENT_SAVES {
+            self.recent_saves.pop_front();
+        }
+        self.recent_saves.push_back(record);
+    }
+
+    // レート制限ヘッダーで指示された待機時間を記録する (既存の待機より長い場合のみ更新)
+    pub fn defer_until(&mut self, wait: ChronoDuration) {
+        let Some(until) = Local::now().naive_local().checked_add_signed(wait) else {
+            self.set_error(format!("レート制限の待機時間が大きすぎるため無視しました ({}秒)", wait.num_seconds()));
+            return;
+        };
+        if self.rate_limit_until.is_some_and(|current| current >= until) {
+            return;
+        }
+        self.rate_limit_until = Some(until);
+        self.set_status_message(format!(
+            "レート制限ヘッダーにより、次回の実行を{}まで延期します (あと{}秒)",
+            until.format("%H:%M:%S"),
+            wait.num_seconds()
+        ));
+    }
+
+    // レート制限による待機中かどうか。待機期間が過ぎていれば解除して false を返す
+    pub fn is_rate_limited(&mut self, now: NaiveDateTime) -> bool {
+        match self.rate_limit_until {
+            Some(until) if now < until => true,
+            Some(_) => {
+                self.rate_limit_until = None;
+                self.set_status_message("レート制限の待機期間が終了しました。".to_string());
+                false
+            }
+            None => false,
+        }
+    }
+
+    // 終了時に表示するサマリー
+    pub fn exit_summary(&self) -> String {
+        let mut summary = "budilnik を終了しました。".to_string();
+        if let Some(run_name) = &self.config.run_name {
+            summary.push_str(&format!(" 実行名: {}", run_name));
+        }
+        if let Some(dir) = &self.today_json_dir {
+            summary.push_str(&format!(" 保存先: {}", dir.display()));
+        }
+        match self.exit_code() {
+            EXIT_CODE_CALL_FAILED => summary.push_str(&format!(" 最後のAPI呼び出しが失敗したため、終了コード {} で終了します。", EXIT_CODE_CALL_FAILED)),
+            EXIT_CODE_SETUP_FAILED => summary.push_str(&format!(" 設定の読み込みまたはセットアップに失敗したため、終了コード {} で終了します。", EXIT_CODE_SETUP_FAILED)),
+            _ => {}
+        }
+        summary
+    }
+
+    /// プロセスの終了コード: セットアップの失敗を最優先し、次に最後の呼び出しの結果を見る
+    /// (まだ呼び出していない場合は正常終了とみなす)
+    pub fn exit_code(&self) -> u8 {
+        if self.setup_failed {
+            EXIT_CODE_SETUP_FAILED
+        } else if self.last_call_ok == Some(false) {
+            EXIT_CODE_CALL_FAILED
+        } else {
+            EXIT_CODE_OK
+        }
+    }
+
     pub fn set_next_trigger_time(&mut self) {
-        let now = Local::now();
-        let target_time = NaiveTime::from_hms_opt(self.initial_h, self.initial_m, self.initial_s)
+        self.set_next_trigger_time_at(Local::now().naive_local());
+    }
+
+    // 時刻が巻き戻っても、すでに実行した実行時刻を再び選ばないようにする (二重実行の防止)
+    fn set_next_trigger_time_at(&mut self, now: NaiveDateTime) {
+        let from = self.last_fired_trigger.map_or(now, |fired| fired.max(now));
+        self.next_trigger_time = Some(self.next_trigger_after(from));
+    }
+
+    // 前回の tick からの経過時間を単調増加する時計と壁時計で比べ、差が大きければシステム時刻が変更されたとみなす
+    // (NTP による補正や夏時間の切り替えなど)。定刻モードでは次の実行時刻を計算し直す
+    pub fn check_clock_adjustment(&mut self, now: NaiveDateTime, instant: Instant) {
+        let Some((previous_instant, previous_now)) = self.last_tick.replace((instant, now)) else {
+            return;
+        };
+        let elapsed = ChronoDuration::from_std(instant.duration_since(previous_instant)).unwrap_or(ChronoDuration::zero());
+        let drift = now.signed_duration_since(previous_now) - elapsed;
+        if drift.num_seconds().abs() < CLOCK_ADJUSTMENT_THRESHOLD_SECS {
+            return;
+        }
+
+        self.add_log(format!("システム時刻の変更を検出しました ({:+}秒)。", drift.num_seconds()));
+        // 時刻が進んで実行時刻を過ぎた場合は、そのまま1回だけ実行する
+        if self.mode == AppMode::OnTimeMode && self.next_trigger_time.is_some_and(|next_trigger| next_trigger > now) {
+            self.set_next_trigger_time_at(now);
+            if let Some(next_trigger) = self.next_trigger_time {
+                self.add_log(format!("次の実行時刻を計算し直しました: {}", next_trigger.format("%Y-%m-%d %H:%M:%S")));
+            }
+        }
+    }
+
+    // 定刻モードの実行時刻の一覧 (早い順)
+    pub fn times_of_day(&self) -> Vec<NaiveTime> {
+        if !self.trigger_times.is_empty() {
+            return self.trigger_times.clone();
+        }
+        let time = NaiveTime::from_hms_opt(self.initial_h, self.initial_m, self.initial_s)
             .unwrap_or_else(|| NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        vec![time]
+    }
 
-        let mut next_trigger = now.naive_local().date().and_time(target_time);
+    // now より後で、実行が許可された曜日の最も近い実行時刻 (weekdays が空なら毎日)
+    // 今日の実行時刻をすべて過ぎていれば、翌日以降の最も早い実行時刻になる
+    fn next_trigger_after(&self, now: NaiveDateTime) -> NaiveDateTime {
+        if let Some(schedule) = &self.cron_schedule
+            && let Some(now_local) = Local.from_local_datetime(&now).earliest()
+            && let Some(next_trigger) = schedule.after(&now_local).next()
+        {
+            return next_trigger.naive_local();
+        }
+        let times = self.times_of_day();
+        let weekdays = &self.config.weekdays;
+        let is_allowed = |date: NaiveDate| weekdays.is_empty() || weekdays.iter().any(|allowed| allowed.0 == date.weekday());
 
-        if next_trigger <= now.naive_local() {
-            next_trigger += ChronoDuration::days(1);
+        // 曜日の指定があっても、8日先までには必ず見つかる
+        for days in 0..8 {
+            let Some(date) = now.date().checked_add_days(Days::new(days)) else {
+                break;
+            };
+            if !is_allowed(date) {
+                continue;
+            }
+            if let Some(next_trigger) = times.iter().map(|time| date.and_time(*time)).find(|candidate| *candidate > now) {
+                return next_trigger;
+            }
+        }
+        now.date().and_time(times[0])
+    }
+
+    // 今後 count 回分の実行予定時刻 (定刻モードは設定時刻や cron から、クロックモードは残り時間と間隔から求める)
+    pub fn upcoming_runs(&self, now: NaiveDateTime, count: usize) -> Vec<NaiveDateTime> {
+        let mut runs = Vec::with_capacity(count);
+        // 表せる日時の範囲を超える場合は、そこで予定の計算をやめる
+        let Some(mut next_run) = (match self.mode {
+            AppMode::OnTimeMode => Some(self.next_trigger_time.filter(|next| *next > now).unwrap_or_else(|| self.next_trigger_after(now))),
+            AppMode::ClockMode => now.checked_add_signed(self.remaining_duration.max(ChronoDuration::zero())),
+        }) else {
+            return runs;
+        };
+        while runs.len() < count {
+            runs.push(next_run);
+            let following = match self.mode {
+                AppMode::OnTimeMode => Some(self.next_trigger_after(next_run)),
+                AppMode::ClockMode => next_run.checked_add_signed(self.total_duration),
+            };
+            // 間隔が 0 の場合などに同じ時刻を繰り返さない
+            match following {
+                Some(following) if following > next_run => next_run = following,
+                _ => break,
+            }
+        }
+        runs
+    }
+
+    // 接続状態 (最後の呼び出しの結果と、最後に成功してからの経過時間から判定する)
+    // 最後の成功の後の実行予定 (クロックモードでは成功時刻 + 間隔) に猶予を加えた時刻を過ぎたら Stale とする
+    pub fn health(&self, now: NaiveDateTime) -> Health {
+        let last_success_at = match (self.last_call_ok, self.last_success_at) {
+            (Some(false), _) => return Health::Failing,
+            (Some(true), Some(last_success_at)) => last_success_at,
+            _ => return Health::Unknown,
+        };
+        let next_expected = match self.mode {
+            AppMode::OnTimeMode => Some(self.next_trigger_after(last_success_at)),
+            AppMode::ClockMode => last_success_at.checked_add_signed(self.total_duration),
+        };
+        match next_expected.and_then(|next| next.checked_add_signed(ChronoDuration::seconds(HEALTH_GRACE_SECS))) {
+            Some(deadline) if now > deadline => Health::Stale,
+            _ => Health::Healthy,
         }
-        self.next_trigger_time = Some(next_trigger);
     }
 
     pub fn reset_timer(&mut self) {
         self.remaining_duration = self.total_duration;
     }
 
-    pub fn decrement_timer(&mut self) {
-        self.remaining_duration = self.remaining_duration - ChronoDuration::seconds(1);
-        if self.remaining_duration.num_seconds() < 0 {
-            self.remaining_duration = ChronoDuration::seconds(0);
+    // クロックモードの場合、保存先と現在の残り時間を返す (状態ファイルへの書き出し用)
+    pub fn clock_state(&self) -> Option<(PathBuf, ClockState)> {
+        if self.mode != AppMode::ClockMode {
+            return None;
+        }
+        let path = self.clock_state_path.clone()?;
+        Some((path, ClockState {
+            total_seconds: self.total_duration.num_seconds(),
+            remaining_seconds: self.remaining_duration.num_seconds(),
+        }))
+    }
+
+    // 保存された残り時間を復元する。設定された間隔が変わっている場合は復元せず false を返す
+    pub fn restore_clock_state(&mut self, state: &ClockState) -> bool {
+        match state.remaining_for(self.total_duration.num_seconds()) {
+            Some(remaining_seconds) => {
+                self.remaining_duration = ChronoDuration::seconds(remaining_seconds);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // クロックモードの残り時間を elapsed だけ減らす (0 未満にはしない)
+    pub fn decrement_timer(&mut self, elapsed: ChronoDuration) {
+        self.remaining_duration = (self.remaining_duration - elapsed).max(ChronoDuration::zero());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ScheduleWeekday, DEFAULT_MAX_LOGS};
+    use chrono::{Timelike, Weekday};
+
+    fn clock_app(h: u32, m: u32, s: u32) -> App {
+        App::new(AppMode::ClockMode, h, m, s, Vec::new(), Arc::new(Config::default()))
+    }
+
+    #[test]
+    fn duration_from_extreme_hms_does_not_panic() {
+        let app = clock_app(1_000_000, 0, 0);
+        assert_eq!(app.total_duration, ChronoDuration::hours(1_000_000));
+
+        let app = clock_app(u32::MAX, u32::MAX, u32::MAX);
+        assert_eq!(app.total_duration.num_seconds(), u32::MAX as i64 * 3661);
+    }
+
+    #[test]
+    fn defer_until_ignores_wait_beyond_representable_time() {
+        let mut app = clock_app(0, 1, 0);
+        app.defer_until(ChronoDuration::MAX);
+        assert!(app.rate_limit_until.is_none());
+
+        app.defer_until(ChronoDuration::seconds(30));
+        assert!(app.rate_limit_until.is_some());
+    }
+
+    #[test]
+    fn next_trigger_skips_disallowed_weekdays() {
+        let config = Config {
+            weekdays: vec![ScheduleWeekday(Weekday::Mon), ScheduleWeekday(Weekday::Wed)],
+            ..Config::default()
+        };
+        let app = App::new(AppMode::OnTimeMode, 9, 0, 0, Vec::new(), Arc::new(config));
+
+        // 2025-06-09 は月曜日。当日の実行時刻を過ぎたら次は水曜日
+        let monday_morning = NaiveDate::from_ymd_opt(2025, 6, 9).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        assert_eq!(app.next_trigger_after(monday_morning), monday_morning.with_hour(9).unwrap());
+        let monday_noon = monday_morning.with_hour(12).unwrap();
+        assert_eq!(app.next_trigger_after(monday_noon), NaiveDate::from_ymd_opt(2025, 6, 11).unwrap().and_hms_opt(9, 0, 0).unwrap());
+
+        // 水曜日を過ぎたら翌週の月曜日
+        let thursday = NaiveDate::from_ymd_opt(2025, 6, 12).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        assert_eq!(app.next_trigger_after(thursday), NaiveDate::from_ymd_opt(2025, 6, 16).unwrap().and_hms_opt(9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn next_trigger_without_weekdays_is_every_day() {
+        let app = App::new(AppMode::OnTimeMode, 9, 0, 0, Vec::new(), Arc::new(Config::default()));
+        let saturday_noon = NaiveDate::from_ymd_opt(2025, 6, 14).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(app.next_trigger_after(saturday_noon), NaiveDate::from_ymd_opt(2025, 6, 15).unwrap().and_hms_opt(9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn next_trigger_picks_nearest_of_multiple_times() {
+        let mut app = App::new(AppMode::OnTimeMode, 9, 0, 0, Vec::new(), Arc::new(Config::default()));
+        app.trigger_times = ["09:00:00", "13:00:00", "18:00:00"].iter()
+            .map(|time| NaiveTime::parse_from_str(time, "%H:%M:%S").unwrap())
+            .collect();
+
+        let day = NaiveDate::from_ymd_opt(2025, 6, 9).unwrap();
+        assert_eq!(app.next_trigger_after(day.and_hms_opt(10, 0, 0).unwrap()), day.and_hms_opt(13, 0, 0).unwrap());
+        assert_eq!(app.next_trigger_after(day.and_hms_opt(13, 0, 0).unwrap()), day.and_hms_opt(18, 0, 0).unwrap());
+        // すべて過ぎていれば翌日の最も早い時刻
+        assert_eq!(app.next_trigger_after(day.and_hms_opt(19, 0, 0).unwrap()), day.succ_opt().unwrap().and_hms_opt(9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn decrement_timer_saturates_at_zero() {
+        let mut app = clock_app(0, 0, 1);
+        app.decrement_timer(ChronoDuration::milliseconds(600));
+        assert_eq!(app.remaining_duration, ChronoDuration::milliseconds(400));
+        app.decrement_timer(ChronoDuration::milliseconds(600));
+        assert_eq!(app.remaining_duration, ChronoDuration::zero());
+    }
+
+    #[test]
+    fn restore_clock_state_only_when_interval_unchanged() {
+        let mut app = clock_app(0, 1, 0);
+        assert!(app.restore_clock_state(&ClockState { total_seconds: 60, remaining_seconds: 25 }));
+        assert_eq!(app.remaining_duration, ChronoDuration::seconds(25));
+
+        // 間隔が変わっていれば最初から数え直す
+        let mut app = clock_app(0, 2, 0);
+        assert!(!app.restore_clock_state(&ClockState { total_seconds: 60, remaining_seconds: 25 }));
+        assert_eq!(app.remaining_duration, ChronoDuration::seconds(120));
+    }
+
+    #[test]
+    fn toggle_mode_rejects_time_invalid_for_on_time() {
+        let mut app = clock_app(0, 1, 30);
+        app.toggle_mode();
+        assert_eq!(app.mode, AppMode::OnTimeMode);
+        assert!(app.next_trigger_time.is_some());
+
+        app.toggle_mode();
+        assert_eq!(app.mode, AppMode::ClockMode);
+        assert_eq!(app.remaining_duration, ChronoDuration::seconds(90));
+
+        // 25時は定刻モードでは使えないため、クロックモードのまま
+        let mut app = clock_app(25, 0, 0);
+        app.toggle_mode();
+        assert_eq!(app.mode, AppMode::ClockMode);
+    }
+
+    #[test]
+    fn clock_moving_backward_does_not_fire_twice() {
+        let mut app = App::new(AppMode::OnTimeMode, 9, 0, 0, Vec::new(), Arc::new(Config::default()));
+        let day = NaiveDate::from_ymd_opt(2025, 6, 9).unwrap();
+        let start = Instant::now();
+
+        // 9:00 に実行した直後、時刻が 8:59:00 に巻き戻る
+        app.last_fired_trigger = Some(day.and_hms_opt(9, 0, 0).unwrap());
+        app.set_next_trigger_time_at(day.and_hms_opt(9, 0, 1).unwrap());
+        app.check_clock_adjustment(day.and_hms_opt(9, 0, 1).unwrap(), start);
+        app.check_clock_adjustment(day.and_hms_opt(8, 59, 0).unwrap(), start + std::time::Duration::from_secs(1));
+        assert_eq!(app.next_trigger_time, Some(day.succ_opt().unwrap().and_hms_opt(9, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn logs_below_min_log_level_are_suppressed() {
+        let config = Config { min_log_level: LogLevel::Warn, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        app.add_debug_log("debug".to_string());
+        app.add_log("info".to_string());
+        app.add_log_with_level(LogLevel::Warn, "warn".to_string());
+        app.set_error("error".to_string());
+
+        let levels: Vec<LogLevel> = app.logs.iter().map(|record| record.level).collect();
+        assert_eq!(levels, vec![LogLevel::Warn, LogLevel::Error]);
+        assert!(app.logs[0].text().ends_with(": WARN: warn"));
+    }
+
+    #[test]
+    fn quit_requires_confirmation_when_enabled() {
+        let key = |c| CrosstermEvent::Key(crossterm::event::KeyEvent::from(KeyCode::Char(c)));
+        let config = Config { confirm_quit: true, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        app.handle_event(&key('q'));
+        assert!(app.running && app.is_quit_confirming);
+        app.handle_event(&key('n'));
+        assert!(app.running && !app.is_quit_confirming);
+        app.handle_event(&key('q'));
+        app.handle_event(&key('y'));
+        assert!(!app.running);
+
+        let config = Config { confirm_quit: false, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        app.handle_event(&key('q'));
+        assert!(!app.running);
+    }
+
+    #[test]
+    fn error_notifications_are_rate_limited() {
+        let config = Config { notify_on_error: true, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        let start = Instant::now();
+        assert!(app.should_notify_error(start));
+        assert!(!app.should_notify_error(start + Duration::from_secs(10)));
+        assert!(app.should_notify_error(start + ERROR_NOTIFICATION_INTERVAL));
+
+        assert!(!clock_app(0, 1, 0).should_notify_error(start));
+    }
+
+    #[test]
+    fn reaching_max_calls_stops_the_app() {
+        let config = Config { max_calls: Some(2), ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        app.record_completed_fetch();
+        assert!(app.running);
+        app.record_completed_fetch();
+        assert!(!app.running);
+        assert_eq!(app.status_message.as_deref(), Some("指定回数に達したため終了します"));
+
+        let config = Config { max_calls: Some(0), ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        app.record_completed_fetch();
+        assert!(app.running);
+    }
+
+    #[test]
+    fn logs_are_trimmed_to_configured_max_logs() {
+        let config = Config { max_logs: 3, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        for i in 0..5 {
+            app.add_log(format!("ログ {}", i));
+        }
+        let messages: Vec<&str> = app.logs.iter().map(|record| record.message.as_str()).collect();
+        assert_eq!(messages, ["ログ 2", "ログ 3", "ログ 4"]);
+
+        assert_eq!(clock_app(0, 1, 0).max_logs, DEFAULT_MAX_LOGS);
+    }
+
+    #[test]
+    fn health_follows_last_result_and_interval() {
+        let mut app = clock_app(0, 10, 0);
+        let now = Local::now().naive_local();
+        assert_eq!(app.health(now), Health::Unknown);
+
+        app.record_call_result(true);
+        let last_success_at = app.last_success_at.unwrap();
+        assert_eq!(app.health(last_success_at + ChronoDuration::minutes(10)), Health::Healthy);
+        assert_eq!(app.health(last_success_at + ChronoDuration::minutes(12)), Health::Stale);
+
+        app.record_call_result(false);
+        assert_eq!(app.health(last_success_at), Health::Failing);
+    }
+
+    #[test]
+    fn cron_schedule_overrides_time_of_day() {
+        let config = Config { on_time: true, cron: Some("0 */15 * * * *".to_string()), ..Config::default() };
+        let app = App::new(AppMode::OnTimeMode, 9, 0, 0, Vec::new(), Arc::new(config));
+        let now = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(10, 7, 30).unwrap();
+        assert_eq!(app.next_trigger_after(now), NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(10, 15, 0).unwrap());
+    }
+
+    #[test]
+    fn date_rollover_switches_to_new_day_directory_once() {
+        let mut app = clock_app(0, 1, 0);
+        app.today_json_dir = Some(PathBuf::from("jsons").join("2024-05-01"));
+        let day = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let next_day = day.succ_opt().unwrap();
+        assert_eq!(app.begin_date_rollover(day), None);
+
+        assert_eq!(app.begin_date_rollover(next_day), Some(PathBuf::from("jsons")));
+        assert_eq!(app.begin_date_rollover(next_day), None);
+        app.finish_date_rollover(PathBuf::from("jsons").join("2024-05-02"), Vec::new());
+        assert_eq!(app.today_json_dir, Some(PathBuf::from("jsons").join("2024-05-02")));
+        assert!(app.status_message.as_deref().unwrap().starts_with("日付が変わりました"));
+    }
+
+    #[test]
+    fn resize_clamps_log_scroll_immediately() {
+        let mut app = clock_app(0, 1, 0);
+        for i in 0..100 {
+            app.add_log(format!("ログ {}", i));
         }
+        app.is_log_auto_scroll = false;
+        app.log_scroll = 95;
+        app.handle_event(&CrosstermEvent::Resize(80, 60));
+        assert!(app.log_view_height > 0);
+        assert_eq!(app.log_scroll, 100 - app.log_view_height);
+
+        app.is_log_auto_scroll = true;
+        app.handle_event(&CrosstermEvent::Resize(80, 40));
+        assert_eq!(app.log_scroll, 100 - app.log_view_height);
+    }
+
+    #[test]
+    fn yank_without_saved_file_logs_message() {
+        let mut app = clock_app(0, 1, 0);
+        app.handle_event(&CrosstermEvent::Key(KeyCode::Char('y').into()));
+        assert_eq!(app.logs.back().unwrap().message, "保存済みファイルがありません");
+        assert!(app.clipboard.is_none());
+    }
+
+    #[test]
+    fn exit_code_reflects_last_call_and_setup() {
+        let mut app = clock_app(0, 1, 0);
+        assert_eq!(app.exit_code(), EXIT_CODE_OK);
+
+        app.record_call_result(false);
+        assert_eq!(app.exit_code(), EXIT_CODE_CALL_FAILED);
+        app.record_call_result(true);
+        assert_eq!(app.exit_code(), EXIT_CODE_OK);
+
+        app.setup_failed = true;
+        assert_eq!(app.exit_code(), EXIT_CODE_SETUP_FAILED);
+        assert!(app.exit_summary().contains("終了コード 2"));
+    }
+
+    #[test]
+    fn restored_stats_keep_counting_across_restarts() {
+        let mut app = clock_app(0, 1, 0);
+        app.restore_stats(&SavedStats { calls_total: 10, calls_ok: 8, calls_err: 2, last_success_at: None });
+        app.record_call_result(true);
+        app.record_call_result(false);
+
+        let stats = app.saved_stats();
+        assert_eq!((stats.calls_total, stats.calls_ok, stats.calls_err), (12, 9, 3));
+        assert!(stats.last_success_at.is_some());
+        // 終了コードは今回の実行の結果だけで決まる
+        assert_eq!(app.exit_code(), EXIT_CODE_CALL_FAILED);
+    }
+
+    #[test]
+    fn min_interval_skips_requests_sent_too_soon() {
+        let config = Config { min_interval_secs: 10, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        let now = Instant::now();
+        assert!(app.min_interval_elapsed("https://a.example", now));
+
+        app.record_request_sent("https://a.example", now);
+        assert!(!app.min_interval_elapsed("https://a.example", now + Duration::from_secs(9)));
+        assert!(app.min_interval_elapsed("https://a.example", now + Duration::from_secs(10)));
+        // エンドポイントごとに判定する
+        assert!(app.min_interval_elapsed("https://b.example", now));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn manual_trigger_is_ignored_during_cooldown() {
+        let config = Config { manual_cooldown_secs: 2, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, vec!["https://a.example".to_string()], Arc::new(config));
+        app.today_json_dir = Some(PathBuf::from("jsons"));
+        let now = Instant::now();
+        assert!(app.accept_manual_trigger(now));
+        assert!(!app.accept_manual_trigger(now + Duration::from_millis(1500)));
+        assert!(app.accept_manual_trigger(now + Duration::from_secs(2)));
+        assert!(app.logs.iter().any(|record| record.message.contains("手動の実行を無視しました")));
+
+        // キーの連打では最初の1回だけ実行する
+        app.last_manual_trigger_at = None;
+        assert!(matches!(app.handle_event(&CrosstermEvent::Key(KeyCode::Char('r').into())), Some(AppAction::TriggerNow(_))));
+        assert!(app.handle_event(&CrosstermEvent::Key(KeyCode::Char('r').into())).is_none());
+    }
+
+    #[test]
+    fn upcoming_runs_stop_at_datetime_limit() {
+        let mut app = clock_app(0, 10, 0);
+        app.remaining_duration = ChronoDuration::seconds(30);
+        assert_eq!(app.upcoming_runs(NaiveDateTime::MAX - ChronoDuration::seconds(60), 3), vec![NaiveDateTime::MAX - ChronoDuration::seconds(30)]);
+        assert!(app.upcoming_runs(NaiveDateTime::MAX, 3).is_empty());
+    }
+
+    #[test]
+    fn upcoming_runs_project_schedule() {
+        let now = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+
+        let mut app = clock_app(0, 10, 0);
+        app.remaining_duration = ChronoDuration::seconds(30);
+        assert_eq!(app.upcoming_runs(now, 3), vec![
+            now + ChronoDuration::seconds(30),
+            now + ChronoDuration::seconds(630),
+            now + ChronoDuration::seconds(1230),
+        ]);
+
+        let mut app = App::new(AppMode::OnTimeMode, 9, 0, 0, Vec::new(), Arc::new(Config::default()));
+        app.trigger_times = vec![NaiveTime::from_hms_opt(9, 0, 0).unwrap(), NaiveTime::from_hms_opt(18, 0, 0).unwrap()];
+        let day = now.date();
+        assert_eq!(app.upcoming_runs(now, 3), vec![
+            day.and_hms_opt(18, 0, 0).unwrap(),
+            day.succ_opt().unwrap().and_hms_opt(9, 0, 0).unwrap(),
+            day.succ_opt().unwrap().and_hms_opt(18, 0, 0).unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn export_logs_writes_every_line_to_timestamped_file() {
+        let dir = std::env::temp_dir().join(format!("budilnik-export-test-{}", std::process::id()));
+        let config = Config { output_dir: dir.clone(), ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        app.add_log("1行目".to_string());
+        app.add_log("2行目".to_string());
+        app.filter_query = Some("2行目".to_string());
+
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+        app.export_logs(now);
+        let content = std::fs::read_to_string(dir.join("log-20240501-093000.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let content = content.unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains("1行目") && content.contains("2行目"));
+        assert!(app.logs.back().unwrap().message.contains("log-20240501-093000.txt"));
+    }
+
+    #[test]
+    fn status_summary_orders_by_count_then_status() {
+        let mut app = clock_app(0, 1, 0);
+        for status in [503, 200, 404, 200, 503, 200] {
+            app.record_status(status);
+        }
+        assert_eq!(app.status_summary(), vec![(200, 3), (503, 2), (404, 1)]);
+    }
+
+    #[test]
+    fn clear_logs_leaves_single_line() {
+        let mut app = clock_app(0, 1, 0);
+        app.set_log_view_height(10);
+        for i in 0..30 {
+            app.add_log(format!("ログ {}", i));
+        }
+        app.log_scroll = 10;
+        app.is_log_auto_scroll = false;
+
+        app.handle_event(&CrosstermEvent::Key(KeyCode::Char('c').into()));
+        assert_eq!(app.logs.len(), 1);
+        assert_eq!(app.logs.back().unwrap().message, "ログをクリアしました");
+        assert_eq!(app.log_scroll, 0);
+        assert!(app.is_log_auto_scroll);
+    }
+}
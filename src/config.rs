@@ -1,23 +1,723 @@
-use std::{fs, path::Path};
+use std::{borrow::Cow, collections::{BTreeMap, HashMap}, fs, net::{IpAddr, Ipv4Addr, Ipv6Addr}, path::{Path, PathBuf}, str::FromStr, sync::Arc};
 
 use anyhow::{anyhow, Result};
+use chrono::{NaiveTime, Timelike, Weekday};
+use chrono::format::{Item, StrftimeItems};
+use cron::Schedule;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use reqwest::Method;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::app::LogLevel;
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct TimeConfig {
     pub h: u32,
     pub m: u32,
     pub s: u32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// 画面に保持するログの最大行数の既定値
+pub const DEFAULT_MAX_LOGS: usize = 256;
+
+/// リクエストの User-Agent の既定値 (例: budilnik/0.1.0)
+pub const DEFAULT_USER_AGENT: &str = concat!("budilnik/", env!("CARGO_PKG_VERSION"));
+
+/// 保存するファイル名の既定のテンプレート (例: 09-00-00.json)
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "%H-%M-%S";
+
+/// 実行時刻を確認する間隔の下限 (短すぎるとCPUを使い続けるため)
+const MIN_CHECK_INTERVAL_MS: u64 = 10;
+
+/// クロックモードで設定できる間隔の上限 (365日)
+pub const MAX_INTERVAL_SECS: u64 = 365 * 24 * 60 * 60;
+
+impl TimeConfig {
+    /// 設定された時間の合計秒数 (u64 で計算するため、どの値でもオーバーフローしない)
+    pub fn total_seconds(&self) -> u64 {
+        self.h as u64 * 3600 + self.m as u64 * 60 + self.s as u64
+    }
+
+    /// 定刻モード / クロックモードそれぞれの制約を満たしているか検証する
+    /// (設定ファイルの読み込み時と、TUIでの時刻・間隔の編集時に共通で使う)
+    pub fn validate(&self, on_time: bool) -> Result<()> {
+        // 定刻モードの場合のバリデーション
+        if on_time {
+            if self.h >= 24 {
+                return Err(anyhow!("設定エラー: 定刻モードでは 'time.h' は24未満である必要があります (現在: {})", self.h));
+            }
+            // 定刻モードではMとSは常に60未満
+            if self.m >= 60 {
+                return Err(anyhow!("設定エラー: 定刻モードでは 'time.m' は60未満である必要があります (現在: {})", self.m));
+            }
+            if self.s >= 60 {
+                return Err(anyhow!("設定エラー: 定刻モードでは 'time.s' は60未満である必要があります (現在: {})", self.s));
+            }
+        } else { // クロックモードの場合
+            // クロックモードのmとsの制限解除ロジック
+            let h_is_zero = self.h == 0;
+            let m_is_zero = self.m == 0;
+            let s_is_zero = self.s == 0;
+
+            // h=0, m=0 の場合、sの60制限を解除
+            if ! (h_is_zero && m_is_zero) && self.s >= 60 {
+                return Err(anyhow!("設定エラー: クロックモードでは 'time.s' は60未満である必要があります (現在: {})", self.s));
+            }
+            // h=0, s=0 の場合、mの60制限を解除
+            if ! (h_is_zero && s_is_zero) && self.m >= 60 {
+                return Err(anyhow!("設定エラー: クロックモードでは 'time.m' は60未満である必要があります (現在: {})", self.m));
+            }
+            // 極端に長い間隔は期限の計算で桁あふれする恐れがあるため上限を設ける
+            if self.total_seconds() > MAX_INTERVAL_SECS {
+                return Err(anyhow!(
+                    "設定エラー: クロックモードの間隔は最大{}時間までです (現在: {}秒)",
+                    MAX_INTERVAL_SECS / 3600,
+                    self.total_seconds()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 定刻モードの実行時刻 ("HH:MM:SS" 形式の文字列)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(value.trim(), "%H:%M:%S")
+            .map(TimeOfDay)
+            .map_err(|_| format!("時刻は \"HH:MM:SS\" の形式で指定してください (現在: {:?})", value))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> String {
+        time.0.format("%H:%M:%S").to_string()
+    }
+}
+
+/// time の設定 (時・分・秒のオブジェクト、または定刻モードでの複数の実行時刻)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TimeSetting {
+    Single(TimeConfig),
+    Multiple(Vec<TimeOfDay>),
+}
+
+impl Default for TimeSetting {
+    fn default() -> Self {
+        TimeSetting::Single(TimeConfig::default())
+    }
+}
+
+impl TimeSetting {
+    /// 時・分・秒としての値 (複数の実行時刻の場合は最も早い時刻)
+    pub fn primary(&self) -> TimeConfig {
+        match self {
+            TimeSetting::Single(time) => time.clone(),
+            TimeSetting::Multiple(times) => times.iter().min_by_key(|time| time.0)
+                .map(|time| TimeConfig { h: time.0.hour(), m: time.0.minute(), s: time.0.second() })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 定刻モードの実行時刻の一覧 (複数指定した場合のみ。早い順)
+    pub fn times_of_day(&self) -> Vec<NaiveTime> {
+        match self {
+            TimeSetting::Single(_) => Vec::new(),
+            TimeSetting::Multiple(times) => {
+                let mut times: Vec<NaiveTime> = times.iter().map(|time| time.0).collect();
+                times.sort();
+                times.dedup();
+                times
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateLimitConfig {
+    pub capacity: u32,       // バケットの最大トークン数 (連続して送信できるリクエスト数)
+    pub refill_per_sec: f64, // 1秒あたりに補充されるトークン数
+}
+
+// レスポンスの種類 (保存ファイルの拡張子とJSON固有の処理の有無を決める)
+#[derive(Debug, Default, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseType {
+    #[default]
+    Json,   // .json として保存し、JSON固有の処理 (メタデータ埋め込み、エラーエンベロープ判定) を行う
+    Text,   // .txt として保存する
+    Binary, // 受信したバイト列をそのまま .bin として保存する
+}
+
+impl ResponseType {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ResponseType::Json => "json",
+            ResponseType::Text => "txt",
+            ResponseType::Binary => "bin",
+        }
+    }
+
+    pub fn is_json(self) -> bool {
+        self == ResponseType::Json
+    }
+}
+
+// レスポンスの保存形式
+#[derive(Debug, Default, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    #[default]
+    Files, // 呼び出しごとに HHmmss.json などのファイルを作成する
+    Jsonl, // 日付ディレクトリの responses.jsonl に1行ずつ追記する
+}
+
+// 接続に使うIPのバージョン (名前解決の結果のうち、どのアドレスに接続するか)
+#[derive(Debug, Default, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    #[default]
+    Auto, // IPv4 と IPv6 のどちらも使う (名前解決の結果の順)
+    V4,   // IPv4 のアドレスだけに接続する
+    V6,   // IPv6 のアドレスだけに接続する
+}
+
+impl IpVersion {
+    /// 接続元アドレス (この種類のアドレスだけに接続させるために使う)。auto の場合は None
+    pub fn local_address(self) -> Option<IpAddr> {
+        match self {
+            IpVersion::Auto => None,
+            IpVersion::V4 => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpVersion::V6 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        }
+    }
+
+    /// IPのバージョンを固定している場合のログの文言
+    pub fn forced_message(self) -> Option<&'static str> {
+        match self {
+            IpVersion::Auto => None,
+            IpVersion::V4 => Some("IPv4 のアドレスだけに接続します (ip_version: v4)"),
+            IpVersion::V6 => Some("IPv6 のアドレスだけに接続します (ip_version: v6)"),
+        }
+    }
+}
+
+// 呼び出すAPIのエンドポイント (1つの文字列、または複数のエンドポイントの配列)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ApiEndpoints {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Default for ApiEndpoints {
+    fn default() -> Self {
+        ApiEndpoints::Single(String::new())
+    }
+}
+
+impl ApiEndpoints {
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            ApiEndpoints::Single(endpoint) => std::slice::from_ref(endpoint),
+            ApiEndpoints::Multiple(endpoints) => endpoints,
+        }
+    }
+}
+
+/// 定刻モードで実行する曜日 ("Mon" や "monday" のように指定する。大文字小文字は区別しない)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ScheduleWeekday(pub Weekday);
+
+impl TryFrom<String> for ScheduleWeekday {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.trim().parse::<Weekday>()
+            .map(ScheduleWeekday)
+            .map_err(|_| format!("曜日の指定が不正です: {:?} (例: \"Mon\", \"Tue\")", value))
+    }
+}
+
+impl From<ScheduleWeekday> for String {
+    fn from(weekday: ScheduleWeekday) -> String {
+        weekday.0.to_string()
+    }
+}
+
+// リクエストのHTTPメソッド
+#[derive(Debug, Default, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+}
+
+impl HttpMethod {
+    pub fn as_reqwest(self) -> Method {
+        match self {
+            HttpMethod::Get => Method::GET,
+            HttpMethod::Post => Method::POST,
+            HttpMethod::Put => Method::PUT,
+        }
+    }
+}
+
+/// HTTPステータスの条件 ("200" のような個別のコード、または "2xx" のようなクラス)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "Value", into = "String")]
+pub enum StatusPattern {
+    Exact(u16),
+    Class(u16), // 先頭の桁 (2xx なら 2)
+}
+
+impl StatusPattern {
+    pub fn matches(self, status: u16) -> bool {
+        match self {
+            StatusPattern::Exact(code) => status == code,
+            StatusPattern::Class(class) => status / 100 == class,
+        }
+    }
+}
+
+impl TryFrom<Value> for StatusPattern {
+    type Error = String;
+
+    fn try_from(value: Value) -> std::result::Result<Self, Self::Error> {
+        let text = match &value {
+            Value::String(s) => s.trim().to_ascii_lowercase(),
+            Value::Number(n) => n.to_string(),
+            _ => return Err(format!("ステータスの条件は \"200\" や \"2xx\" の形式で指定してください (現在: {})", value)),
+        };
+        if let Some(class) = text.strip_suffix("xx")
+            && let Ok(class @ 1..=5) = class.parse::<u16>()
+        {
+            return Ok(StatusPattern::Class(class));
+        }
+        match text.parse::<u16>() {
+            Ok(code @ 100..=599) => Ok(StatusPattern::Exact(code)),
+            _ => Err(format!("ステータスの条件は \"200\" や \"2xx\" の形式で指定してください (現在: {})", value)),
+        }
+    }
+}
+
+impl From<StatusPattern> for String {
+    fn from(pattern: StatusPattern) -> String {
+        match pattern {
+            StatusPattern::Exact(code) => code.to_string(),
+            StatusPattern::Class(class) => format!("{}xx", class),
+        }
+    }
+}
+
+/// HTTP Basic 認証の資格情報
+#[derive(Clone, Deserialize, Serialize)]
+pub struct BasicAuth {
+    pub username: String,
+    #[serde(default)]
+    pub password: String, // 空でもよい (省略した場合は空のパスワード)
+}
+
+// 設定をデバッグ出力したときにパスワードが表示されないようにする
+impl std::fmt::Debug for BasicAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BasicAuth")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+/// 成功とみなす条件 (HTTPステータスの条件と、任意でJSONポインタの存在・一致の条件)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SuccessCondition {
+    #[serde(default)]
+    pub status: Vec<StatusPattern>, // いずれかに一致すれば成功 (空の場合は 2xx)
+    #[serde(default)]
+    pub json_pointer: Option<String>, // 本文のこのポインタを検査する (例: "/status")
+    #[serde(default)]
+    pub equals: Option<Value>, // 指定した場合は値の一致、省略した場合は存在のみを検査する
+}
+
+impl SuccessCondition {
+    pub fn status_matches(&self, status: u16) -> bool {
+        if self.status.is_empty() {
+            return (200..300).contains(&status);
+        }
+        self.status.iter().any(|pattern| pattern.matches(status))
+    }
+
+    /// 本文の条件を評価する。満たさない場合はその理由を返す
+    pub fn check_body(&self, body: &[u8]) -> std::result::Result<(), String> {
+        let Some(pointer) = self.json_pointer.as_deref() else {
+            return Ok(());
+        };
+        let body: Value = serde_json::from_slice(body).map_err(|_| "レスポンスがJSONではありません".to_string())?;
+        match (body.pointer(pointer), &self.equals) {
+            (None, _) => Err(format!("'{}' が存在しません", pointer)),
+            (Some(actual), Some(expected)) if actual != expected => {
+                Err(format!("'{}' の値が {} ではありません (実際: {})", pointer, expected, actual))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
-    pub api: String,
+    pub api: ApiEndpoints, // 複数指定した場合は、トリガーのたびにすべてのエンドポイントを呼び出す
     pub on_time: bool, // true: 定刻モード, false: クロックモード
-    pub time: TimeConfig,
+    #[serde(default)]
+    pub time: Option<TimeSetting>, // 定刻モードでは "HH:MM:SS" の配列で複数の実行時刻も指定できる (cron を指定した場合は省略できる)
+    #[serde(default)]
+    pub interval: Option<String>, // クロックモードの間隔を "90m" や "1h30m" の形式で指定する (time より優先する)
+    #[serde(default)]
+    pub cron: Option<String>, // 定刻モードの実行スケジュールを cron 式で指定する (time と weekdays より優先)
+    #[serde(default)]
+    pub weekdays: Vec<ScheduleWeekday>, // 定刻モードで実行する曜日 (空の場合は毎日)
+    #[serde(default)]
+    pub embed_meta: bool, // true: 保存するJSONにリクエストのメタデータを埋め込む
+    #[serde(default)]
+    pub require_json: bool, // true: JSONとして解釈できないレスポンスは保存せずに失敗として扱う
+    #[serde(default)]
+    pub pretty_json: bool, // true: 保存するJSONを整形する (JSONでない場合はそのまま保存する)
+    #[serde(default)]
+    pub compress: bool, // true: レスポンスを gzip で圧縮して保存する (.json.gz など)
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>, // 未設定の場合はレート制限なし
+    #[serde(default)]
+    pub skip_unchanged: bool, // 前回保存した本文と同じ内容のレスポンスは保存しない
+    #[serde(default)]
+    pub min_interval_secs: u64, // 同じエンドポイントへのリクエストの最小間隔 (0 なら制限しない)
+    #[serde(default = "default_manual_cooldown_secs")]
+    pub manual_cooldown_secs: u64, // 手動の実行 ('r' / 'R' キー) の後、次の手動の実行を受け付けない秒数 (0 なら制限しない)
+    #[serde(default, alias = "log_level")]
+    pub min_log_level: LogLevel, // これより低いレベルのログは出力しない (debug / info / warn / error)。以前の log_level も使える
+    #[serde(default)]
+    pub error_json_pointer: Option<String>, // 2xxでもこのJSONポインタの値が真ならエラーとして扱う (例: "/error")
+    #[serde(default)]
+    pub extract: Option<String>, // 指定した場合、レスポンスからこの JSONPath に一致する値だけを保存する (例: "$.data.items")
+    #[serde(default)]
+    pub schema_path: Option<PathBuf>, // 指定した場合、この JSON Schema に合うレスポンスだけを保存する
+    #[serde(skip)]
+    pub schema: Option<Arc<jsonschema::Validator>>, // schema_path から読み込んだスキーマ (設定の読み込み時に作成する)
+    #[serde(default)]
+    pub stream_threshold_bytes: Option<u64>, // これを超えるレスポンスは一時ファイル経由で保存する (未設定なら常にメモリ上)
+    #[serde(default)]
+    pub max_response_bytes: Option<u64>, // これを超えるレスポンスは受信を中止して保存しない (未設定なら無制限)
+    #[serde(default)]
+    pub response_type: ResponseType, // レスポンスの種類 (json / text / binary)
+    #[serde(default = "default_rate_limit_backoff_ms")]
+    pub rate_limit_backoff_ms: u64, // 429でRetry-Afterがない場合の待機時間の基準値 (ミリ秒)
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf, // レスポンスを保存するベースディレクトリ (相対パスは作業ディレクトリから)
+    #[serde(default)]
+    pub backup_dir: Option<PathBuf>, // 保存したレスポンスを同じファイル名でこのディレクトリにも書き出す (未設定なら書き出さない)
+    #[serde(default)]
+    pub stats_file: Option<PathBuf>, // 呼び出しの累計を起動時に読み込み、終了時に書き出すファイル (未設定なら起動ごとに0から数える)
+    #[serde(default)]
+    pub log_file: Option<PathBuf>, // ログを追記するファイル (未設定ならファイルに出力しない)
+    #[serde(default)]
+    pub retention_days: Option<u32>, // 起動時にこの日数より古い日付ディレクトリを削除する (未設定なら削除しない)
+    #[serde(default)]
+    pub run_name: Option<String>, // 実行名。保存先を ./jsons/<run_name>/ に分ける
+    #[serde(default)]
+    pub success_when: Option<SuccessCondition>, // 成功とみなす条件 (未設定の場合は 2xx を成功とする)
+    #[serde(default)]
+    pub expected_statuses: Vec<StatusPattern>, // 成功とみなすHTTPステータス (空の場合は 2xx。success_when.status と同じ書式)
+    #[serde(default)]
+    pub method: HttpMethod, // リクエストのメソッド (GET / POST / PUT)
+    #[serde(default)]
+    pub body: Option<String>, // POST / PUT で送信するJSON (Content-Type: application/json)
+    #[serde(default)]
+    pub headers: HashMap<String, String>, // すべてのリクエストに付与するヘッダー (例: Authorization)
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String, // リクエストの User-Agent ヘッダー
+    #[serde(default)]
+    pub ip_version: IpVersion, // 接続に使うIPのバージョン (auto / v4 / v6)
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuth>, // すべてのリクエストに付与する Basic 認証 (headers の Authorization とは併用できない)
+    #[serde(default)]
+    pub query: BTreeMap<String, String>, // すべてのエンドポイントに追加するクエリパラメータ (例: limit=100)
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32, // ネットワークエラーと 5xx の場合に再試行する回数
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64, // リクエストのタイムアウト (秒)。本文の受信完了までを含む
+    #[serde(default = "default_check_interval_ms")]
+    pub check_interval_ms: u64, // 実行時刻・タイマーを確認する間隔 (ミリ秒)。画面の更新 (1秒ごと) とは別
+    #[serde(default = "default_heartbeat_secs")]
+    pub heartbeat_secs: u64, // stderr にハートビートを出力する間隔 (秒)。0 で無効
+    #[serde(default)]
+    pub ca_cert_path: Option<PathBuf>, // 追加で信頼するCA証明書 (PEM)。自己署名のCAを使うAPI向け
+    #[serde(default)]
+    pub http_proxy: Option<String>, // http:// のエンドポイントに使うプロキシ (未指定なら環境変数 HTTP_PROXY)
+    #[serde(default)]
+    pub https_proxy: Option<String>, // https:// のエンドポイントに使うプロキシ (未指定なら環境変数 HTTPS_PROXY)
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool, // true: サーバー証明書を検証しない (テスト用。本番では使わないこと)
+    #[serde(default)]
+    pub dry_run: bool, // true: APIを呼び出さず、呼び出す予定のエンドポイントをログに出すだけにする
+    #[serde(default)]
+    pub save_headers: bool, // true: レスポンスヘッダーを HHmmss.headers.txt に保存する
+    #[serde(default)]
+    pub output_mode: OutputMode, // レスポンスの保存形式 (files / jsonl)
+    #[serde(default = "default_confirm_quit")]
+    pub confirm_quit: bool, // true: 'q' を押したときに終了してよいか確認する (false ならすぐに終了する)
+    #[serde(default)]
+    pub metrics_port: Option<u16>, // 指定した場合、このポートで Prometheus 形式のメトリクス (/metrics) を公開する
+    #[serde(default = "default_metrics_bind")]
+    pub metrics_bind: IpAddr, // メトリクスを待ち受けるアドレス (既定は 127.0.0.1。他のホストから取得する場合は 0.0.0.0)
+    #[serde(default)]
+    pub notify_on_error: bool, // true: API呼び出しが失敗したらデスクトップ通知を出す (最短60秒おき)
+    #[serde(default)]
+    pub webhook_url: Option<String>, // 指定した場合、呼び出しの結果をこのURLに JSON で POST する (Slack/Discord への転送など)
+    #[serde(default)]
+    pub max_calls: Option<u64>, // 初回以外の呼び出しがこの回数完了したら終了する (0 または省略時は無制限)
+    #[serde(default = "default_max_logs")]
+    pub max_logs: usize, // 画面に保持するログの最大行数 (古いものから捨てる)
+    #[serde(default = "default_follow_redirects")]
+    pub follow_redirects: bool, // false: リダイレクトを追わず、3xx を Location ヘッダーと合わせてログに記録する
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize, // リダイレクトを追う最大の回数 (follow_redirects が true の場合のみ)
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String, // 保存するファイル名のテンプレート (strftime の書式と {seq} / {status}。拡張子は response_type に従う)
+    #[serde(default = "default_upcoming_runs")]
+    pub upcoming_runs: usize, // 「今後の実行」パネルに表示する実行予定の件数 (0 ならパネルを表示しない)
+    #[serde(default)]
+    pub startup_delay_secs: u64, // 起動してから初回のAPI呼び出しまで待つ秒数 (0 ならすぐに呼び出す)
+    #[serde(default)]
+    pub no_alt_screen: bool, // 代替スクリーンを使わずに描画する (終了後も出力がスクロールバックに残る)
+    #[serde(default)]
+    pub compact_ui: bool, // true: 起動時からコンパクト表示にする (上部の情報を1行にまとめ、ログを広く表示する)
+    #[serde(default)]
+    pub save_first_response: bool, // true: 初回の呼び出しもステータスの確認だけでなく、レスポンスを保存する
+    #[serde(default)]
+    pub save_raw_bytes: bool, // true: response_type を binary にする (受信したバイト列をそのまま .bin に保存する)
+}
+
+// 設定ファイルを空にした場合 (serde の既定値) と同じ値にする
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            api: Default::default(),
+            on_time: false,
+            time: None,
+            interval: None,
+            cron: None,
+            weekdays: Vec::new(),
+            embed_meta: false,
+            require_json: false,
+            pretty_json: false,
+            compress: false,
+            rate_limit: None,
+            skip_unchanged: false,
+            min_interval_secs: 0,
+            manual_cooldown_secs: default_manual_cooldown_secs(),
+            min_log_level: Default::default(),
+            error_json_pointer: None,
+            extract: None,
+            schema_path: None,
+            schema: None,
+            stream_threshold_bytes: None,
+            max_response_bytes: None,
+            response_type: Default::default(),
+            rate_limit_backoff_ms: default_rate_limit_backoff_ms(),
+            output_dir: default_output_dir(),
+            backup_dir: None,
+            stats_file: None,
+            log_file: None,
+            retention_days: None,
+            run_name: None,
+            success_when: None,
+            expected_statuses: Vec::new(),
+            method: Default::default(),
+            body: None,
+            headers: HashMap::new(),
+            user_agent: default_user_agent(),
+            ip_version: Default::default(),
+            basic_auth: None,
+            query: BTreeMap::new(),
+            max_retries: default_max_retries(),
+            timeout_secs: default_timeout_secs(),
+            check_interval_ms: default_check_interval_ms(),
+            heartbeat_secs: default_heartbeat_secs(),
+            ca_cert_path: None,
+            http_proxy: None,
+            https_proxy: None,
+            danger_accept_invalid_certs: false,
+            dry_run: false,
+            save_headers: false,
+            output_mode: Default::default(),
+            confirm_quit: default_confirm_quit(),
+            metrics_port: None,
+            metrics_bind: default_metrics_bind(),
+            notify_on_error: false,
+            webhook_url: None,
+            max_calls: None,
+            max_logs: default_max_logs(),
+            follow_redirects: default_follow_redirects(),
+            max_redirects: default_max_redirects(),
+            filename_template: default_filename_template(),
+            upcoming_runs: default_upcoming_runs(),
+            startup_delay_secs: 0,
+            no_alt_screen: false,
+            compact_ui: false,
+            save_first_response: false,
+            save_raw_bytes: false,
+        }
+    }
+}
+
+fn default_rate_limit_backoff_ms() -> u64 {
+    60_000
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("./jsons")
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_user_agent() -> String {
+    DEFAULT_USER_AGENT.to_string()
+}
+
+fn default_check_interval_ms() -> u64 {
+    1000
+}
+
+fn default_heartbeat_secs() -> u64 {
+    60
+}
+
+fn default_metrics_bind() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn default_manual_cooldown_secs() -> u64 {
+    2
+}
+
+fn default_max_logs() -> usize {
+    DEFAULT_MAX_LOGS
+}
+
+fn default_follow_redirects() -> bool {
+    true
+}
+
+fn default_max_redirects() -> usize {
+    10
+}
+
+/// "90m" や "1h30m" のような間隔を秒数にする (単位は d / h / m / s。部分の間の空白は無視する)
+/// 形式が正しくない場合や桁あふれする場合は None
+fn parse_interval(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            ' ' if number.is_empty() => {}
+            'd' | 'h' | 'm' | 's' if !number.is_empty() => {
+                let unit = match c {
+                    'd' => 24 * 60 * 60,
+                    'h' => 60 * 60,
+                    'm' => 60,
+                    _ => 1,
+                };
+                total = total.checked_add(number.parse::<u64>().ok()?.checked_mul(unit)?)?;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    // 単位のない数値で終わっている場合は不正とする
+    number.is_empty().then_some(total)
+}
+
+/// schema_path の JSON Schema を読み込み、検証に使えるようにする
+fn load_schema(path: &Path) -> Result<jsonschema::Validator> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("設定エラー: 'schema_path' のファイル '{}' を読み込めません: {}", path.display(), e))?;
+    let schema: Value = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("設定エラー: 'schema_path' のファイル '{}' はJSONとして解釈できません: {}", path.display(), e))?;
+    jsonschema::validator_for(&schema)
+        .map_err(|e| anyhow!("設定エラー: 'schema_path' のファイル '{}' は有効な JSON Schema ではありません: {}", path.display(), e))
+}
+
+fn validate_filename_template(template: &str) -> Result<()> {
+    if template.is_empty() {
+        return Err(anyhow!("設定エラー: 'filename_template' が空です。"));
+    }
+    if template.contains(['/', '\\']) {
+        return Err(anyhow!("設定エラー: 'filename_template' にパスの区切り文字は使えません: {}", template));
+    }
+    if StrftimeItems::new(template).any(|item| matches!(item, Item::Error)) {
+        return Err(anyhow!("設定エラー: 'filename_template' の書式が不正です: {}", template));
+    }
+    Ok(())
+}
+
+fn default_filename_template() -> String {
+    DEFAULT_FILENAME_TEMPLATE.to_string()
+}
+
+fn default_upcoming_runs() -> usize {
+    3
+}
+
+fn default_confirm_quit() -> bool {
+    true
 }
 
 impl Config {
+    /// 起動時に出力する設定の概要 (ヘッダーの値やURLにトークンが含まれることがあるため、値は出力しない)
+    pub fn summary(&self) -> String {
+        let mut header_names: Vec<&str> = self.headers.keys().map(String::as_str).collect();
+        header_names.sort_unstable();
+        format!(
+            "mode={}, endpoints={}, output_dir={}, headers=[{}], basic_auth={}, webhook={}",
+            if self.on_time { "on_time" } else { "clock" },
+            self.api.as_slice().len(),
+            self.output_dir.display(),
+            header_names.join(", "),
+            self.basic_auth.is_some(),
+            self.webhook_url.is_some(),
+        )
+    }
+
+    /// 時・分・秒としての time の値 (cron のみを指定した場合は 0時0分0秒)
+    pub fn primary_time(&self) -> TimeConfig {
+        self.time.as_ref().map(TimeSetting::primary).unwrap_or_default()
+    }
+
+    /// 定刻モードの実行時刻の一覧 (time に複数指定した場合のみ。早い順)
+    pub fn times_of_day(&self) -> Vec<NaiveTime> {
+        self.time.as_ref().map(TimeSetting::times_of_day).unwrap_or_default()
+    }
+
     pub fn load_from_file(path_str: &str) -> Result<Self> {
         let path = Path::new(path_str);
 
@@ -29,38 +729,491 @@ impl Config {
         // ファイルの読み込み
         let content = fs::read_to_string(path)?;
 
-        // JSONのパースとバリデーション
-        let config: Config = serde_json::from_str(&content)
-            .map_err(|e| anyhow!("設定ファイルのパースエラー: {}", e))?;
+        // 拡張子が .toml ならTOML、それ以外はJSONとしてパースする (バリデーションはどちらも同じ)
+        let is_toml = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+        let mut config: Config = if is_toml {
+            toml::from_str(&content).map_err(|e| anyhow!("設定ファイル (TOML) のパースエラー: {}", e))?
+        } else {
+            serde_json::from_str(&content).map_err(|e| anyhow!("設定ファイルのパースエラー: {}", e))?
+        };
 
-        // 定刻モードの場合のバリデーション
-        if config.on_time {
-            if config.time.h >= 24 {
-                return Err(anyhow!("設定エラー: 定刻モードでは 'time.h' は24未満である必要があります (現在: {})", config.time.h));
+        // トークンなどを設定ファイルに書かなくて済むよう、${ENV_VAR} を環境変数の値に置き換える
+        let lookup = |name: &str| std::env::var(name).ok();
+        match &mut config.api {
+            ApiEndpoints::Single(endpoint) => *endpoint = expand_env_vars(endpoint, "api", lookup)?,
+            ApiEndpoints::Multiple(endpoints) => {
+                for endpoint in endpoints.iter_mut() {
+                    *endpoint = expand_env_vars(endpoint, "api", lookup)?;
+                }
             }
-            // 定刻モードではMとSは常に60未満
-            if config.time.m >= 60 {
-                return Err(anyhow!("設定エラー: 定刻モードでは 'time.m' は60未満である必要があります (現在: {})", config.time.m));
+        }
+        for (name, value) in config.headers.iter_mut() {
+            *value = expand_env_vars(value, &format!("headers.{}", name), lookup)?;
+        }
+        if let Some(basic_auth) = config.basic_auth.as_mut() {
+            basic_auth.username = expand_env_vars(&basic_auth.username, "basic_auth.username", lookup)?;
+            basic_auth.password = expand_env_vars(&basic_auth.password, "basic_auth.password", lookup)?;
+        }
+        for (name, value) in config.query.iter_mut() {
+            *value = expand_env_vars(value, &format!("query.{}", name), lookup)?;
+        }
+        if let Some(body) = config.body.as_mut() {
+            *body = expand_env_vars(body, "body", lookup)?;
+        }
+        if let Some(webhook_url) = config.webhook_url.as_mut() {
+            *webhook_url = expand_env_vars(webhook_url, "webhook_url", lookup)?;
+        }
+
+        // cron 式は読み込み時に解釈できることを確認しておく (定刻モードの場合のみ使用できる)
+        if let Some(cron) = &config.cron {
+            if !config.on_time {
+                return Err(anyhow!("設定エラー: 'cron' は定刻モード (on_time: true) の場合のみ使用できます。"));
             }
-            if config.time.s >= 60 {
-                return Err(anyhow!("設定エラー: 定刻モードでは 'time.s' は60未満である必要があります (現在: {})", config.time.s));
+            if !config.weekdays.is_empty() {
+                return Err(anyhow!("設定エラー: 'cron' と 'weekdays' は同時に指定できません。曜日は cron 式で指定してください。"));
             }
-        } else { // クロックモードの場合
-            // クロックモードのmとsの制限解除ロジック
-            let h_is_zero = config.time.h == 0;
-            let m_is_zero = config.time.m == 0;
-            let s_is_zero = config.time.s == 0;
+            parse_cron(cron)?;
+        }
 
-            // h=0, m=0 の場合、sの60制限を解除
-            if ! (h_is_zero && m_is_zero) && config.time.s >= 60 {
-                return Err(anyhow!("設定エラー: クロックモードでは 'time.s' は60未満である必要があります (現在: {})", config.time.s));
+        // interval を指定した場合は、時・分・秒に直して time の代わりに使う
+        if let Some(interval) = &config.interval {
+            if config.on_time {
+                return Err(anyhow!("設定エラー: 'interval' はクロックモード (on_time: false) の場合のみ使用できます。"));
             }
-            // h=0, s=0 の場合、mの60制限を解除
-            if ! (h_is_zero && s_is_zero) && config.time.m >= 60 {
-                return Err(anyhow!("設定エラー: クロックモードでは 'time.m' は60未満である必要があります (現在: {})", config.time.m));
+            let seconds = parse_interval(interval).ok_or_else(|| anyhow!(
+                "設定エラー: 'interval' の形式が正しくありません (現在: {:?})。\"90m\"、\"1h30m\"、\"45s\" のように、数値と単位 (d / h / m / s) を組み合わせて指定してください。",
+                interval
+            ))?;
+            if seconds == 0 {
+                return Err(anyhow!("設定エラー: 'interval' には1秒以上の間隔を指定する必要があります。"));
+            }
+            let h = u32::try_from(seconds / 3600).unwrap_or(u32::MAX);
+            config.time = Some(TimeSetting::Single(TimeConfig { h, m: (seconds % 3600 / 60) as u32, s: (seconds % 60) as u32 }));
+        }
+
+        // 時刻 (定刻モード) または間隔 (クロックモード) のバリデーション
+        match &config.time {
+            None if config.cron.is_none() => {
+                return Err(anyhow!("設定エラー: 'time' を指定する必要があります (定刻モードでは代わりに 'cron' も使えます)。"));
+            }
+            None => {}
+            Some(TimeSetting::Single(time)) => time.validate(config.on_time)?,
+            Some(TimeSetting::Multiple(_)) if !config.on_time => {
+                return Err(anyhow!("設定エラー: 'time' に複数の実行時刻を指定できるのは定刻モードの場合のみです。"));
+            }
+            Some(TimeSetting::Multiple(times)) if times.is_empty() => {
+                return Err(anyhow!("設定エラー: 'time' には1つ以上の実行時刻を指定する必要があります。"));
+            }
+            Some(TimeSetting::Multiple(_)) => {}
+        }
+
+        // リクエスト本文のバリデーション
+        if let Some(body) = &config.body {
+            if config.method == HttpMethod::Get {
+                return Err(anyhow!("設定エラー: 'body' は method が POST または PUT の場合のみ指定できます。"));
+            }
+            if let Err(e) = serde_json::from_str::<&serde_json::value::RawValue>(body) {
+                return Err(anyhow!("設定エラー: 'body' が有効なJSONではありません: {}", e));
+            }
+        }
+
+        // save_raw_bytes は response_type: binary の短縮形。JSONとして扱う設定とは組み合わせられない
+        if config.save_raw_bytes {
+            if config.response_type == ResponseType::Text {
+                return Err(anyhow!("設定エラー: 'save_raw_bytes' と response_type の text は同時に指定できません。"));
+            }
+            if config.require_json {
+                return Err(anyhow!("設定エラー: 'save_raw_bytes' と 'require_json' は同時に指定できません。"));
+            }
+            if config.pretty_json {
+                return Err(anyhow!("設定エラー: 'save_raw_bytes' と 'pretty_json' は同時に指定できません。"));
+            }
+            config.response_type = ResponseType::Binary;
+        }
+
+        if config.require_json && !config.response_type.is_json() {
+            return Err(anyhow!("設定エラー: 'require_json' は response_type が json の場合のみ使用できます。"));
+        }
+
+        // extract は本文をJSONとしてメモリ上で解釈するため、一時ファイル経由の保存とは組み合わせられない
+        if let Some(extract) = &config.extract {
+            if !config.response_type.is_json() {
+                return Err(anyhow!("設定エラー: 'extract' は response_type が json の場合のみ使用できます。"));
+            }
+            if config.stream_threshold_bytes.is_some() {
+                return Err(anyhow!("設定エラー: 'extract' と 'stream_threshold_bytes' は同時に指定できません。"));
+            }
+            if let Err(e) = jsonpath_lib::Compiled::compile(extract) {
+                return Err(anyhow!("設定エラー: 'extract' が有効な JSONPath ではありません ({}): {}", extract, e));
             }
         }
 
+        // スキーマは読み込み時に1度だけ解釈し、呼び出しごとにファイルを読まない
+        if let Some(schema_path) = &config.schema_path {
+            if !config.response_type.is_json() {
+                return Err(anyhow!("設定エラー: 'schema_path' は response_type が json の場合のみ使用できます。"));
+            }
+            if config.stream_threshold_bytes.is_some() {
+                return Err(anyhow!("設定エラー: 'schema_path' と 'stream_threshold_bytes' は同時に指定できません。"));
+            }
+            config.schema = Some(Arc::new(load_schema(schema_path)?));
+        }
+
+        // jsonl では1行に1レスポンスを追記するため、行単位で扱えない保存方法とは組み合わせられない
+        if config.output_mode == OutputMode::Jsonl {
+            if config.response_type == ResponseType::Binary {
+                return Err(anyhow!("設定エラー: output_mode が jsonl の場合、response_type に binary は指定できません。"));
+            }
+            if config.compress {
+                return Err(anyhow!("設定エラー: output_mode が jsonl の場合、'compress' は使用できません。"));
+            }
+            if config.stream_threshold_bytes.is_some() {
+                return Err(anyhow!("設定エラー: output_mode が jsonl の場合、'stream_threshold_bytes' は使用できません。"));
+            }
+            if config.save_headers {
+                return Err(anyhow!("設定エラー: output_mode が jsonl の場合、'save_headers' は使用できません。"));
+            }
+        }
+
+        if config.output_dir.as_os_str().is_empty() {
+            return Err(anyhow!("設定エラー: 'output_dir' が空です。省略した場合は ./jsons に保存します。"));
+        }
+
+        if config.user_agent.trim().is_empty() {
+            return Err(anyhow!("設定エラー: 'user_agent' が空です。省略した場合は {} を送信します。", DEFAULT_USER_AGENT));
+        }
+
+        // どちらも Authorization ヘッダーになるため、一方が黙って上書きされないようにする
+        if config.basic_auth.is_some() && config.headers.keys().any(|name| name.eq_ignore_ascii_case("authorization")) {
+            return Err(anyhow!("設定エラー: 'basic_auth' と headers の Authorization は同時に指定できません。"));
+        }
+
+        if let Some(backup_dir) = &config.backup_dir {
+            if backup_dir.as_os_str().is_empty() {
+                return Err(anyhow!("設定エラー: 'backup_dir' が空です。バックアップしない場合は省略してください。"));
+            }
+            if *backup_dir == config.output_dir {
+                return Err(anyhow!("設定エラー: 'backup_dir' には output_dir と異なるディレクトリを指定してください。"));
+            }
+        }
+
+        // 不正な strftime の書式は保存するときにパニックになるため、読み込み時に検出する
+        validate_filename_template(&config.filename_template)?;
+
+        if config.check_interval_ms < MIN_CHECK_INTERVAL_MS {
+            return Err(anyhow!("設定エラー: 'check_interval_ms' は{}以上である必要があります。", MIN_CHECK_INTERVAL_MS));
+        }
+
+        // 0 だと空の本文以外を受け取れなくなる
+        if config.max_response_bytes == Some(0) {
+            return Err(anyhow!("設定エラー: 'max_response_bytes' は1以上である必要があります。"));
+        }
+
+        // 0 だとログを1行も保持できず、画面にも何も表示されなくなる
+        if config.max_logs == 0 {
+            return Err(anyhow!("設定エラー: 'max_logs' は1以上である必要があります。"));
+        }
+
+        if config.retention_days == Some(0) {
+            return Err(anyhow!("設定エラー: 'retention_days' は1以上である必要があります。削除しない場合は省略してください。"));
+        }
+
+        // タイムアウトのバリデーション (0 だとすべてのリクエストが即座にタイムアウトする)
+        if config.timeout_secs == 0 {
+            return Err(anyhow!("設定エラー: 'timeout_secs' は1以上である必要があります。タイムアウトを長くしたい場合は大きな値を指定してください。"));
+        }
+
+        // CA証明書は起動時に読み込めることを確認しておく (API呼び出しのたびに失敗するのを防ぐ)
+        if let Some(ca_cert_path) = &config.ca_cert_path {
+            let pem = fs::read(ca_cert_path)
+                .map_err(|e| anyhow!("設定エラー: 'ca_cert_path' のファイル '{}' を読み込めません: {}", ca_cert_path.display(), e))?;
+            reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| anyhow!("設定エラー: 'ca_cert_path' のファイル '{}' はPEM形式の証明書ではありません: {}", ca_cert_path.display(), e))?;
+        }
+
+        // Webhook のURLは起動時に書式を確認しておく (呼び出しのたびに警告が出るのを防ぐ)
+        if let Some(webhook_url) = &config.webhook_url {
+            match reqwest::Url::parse(webhook_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => return Err(anyhow!("設定エラー: 'webhook_url' は http:// または https:// で始まるURLである必要があります")),
+            }
+        }
+
+        // レート制限のバリデーション
+        if let Some(rate_limit) = &config.rate_limit {
+            if rate_limit.capacity == 0 {
+                return Err(anyhow!("設定エラー: 'rate_limit.capacity' は1以上である必要があります"));
+            }
+            if !rate_limit.refill_per_sec.is_finite() || rate_limit.refill_per_sec <= 0.0 {
+                return Err(anyhow!("設定エラー: 'rate_limit.refill_per_sec' は0より大きい必要があります (現在: {})", rate_limit.refill_per_sec));
+            }
+        }
+
+        // エンドポイントのバリデーション
+        if config.api.as_slice().is_empty() {
+            return Err(anyhow!("設定エラー: 'api' には1つ以上のエンドポイントを指定する必要があります。"));
+        }
+        if config.api.as_slice().iter().any(|endpoint| endpoint.trim().is_empty()) {
+            return Err(anyhow!("設定エラー: 'api' に空のエンドポイントが含まれています。"));
+        }
+
+        // 成功条件のバリデーション (ステータスの書式は読み込み時に検証済み)
+        // どちらのステータスの条件を使うか曖昧になるため、同時には指定できない
+        if !config.expected_statuses.is_empty()
+            && config.success_when.as_ref().is_some_and(|condition| !condition.status.is_empty())
+        {
+            return Err(anyhow!("設定エラー: 'expected_statuses' と 'success_when.status' は同時に指定できません。"));
+        }
+
+        if let Some(condition) = &config.success_when {
+            match &condition.json_pointer {
+                Some(pointer) if !pointer.is_empty() && !pointer.starts_with('/') => {
+                    return Err(anyhow!("設定エラー: 'success_when.json_pointer' は '/' で始まるJSONポインタである必要があります (現在: {})", pointer));
+                }
+                Some(_) if !config.response_type.is_json() => {
+                    return Err(anyhow!("設定エラー: 'success_when.json_pointer' は response_type が json の場合のみ使用できます"));
+                }
+                None if condition.equals.is_some() => {
+                    return Err(anyhow!("設定エラー: 'success_when.equals' を使うには 'success_when.json_pointer' も指定する必要があります"));
+                }
+                _ => {}
+            }
+        }
+
+        // 実行名はパスの1要素として安全な文字だけに置き換える
+        if let Some(run_name) = config.run_name.take() {
+            let sanitized = sanitize_path_segment(&run_name);
+            if sanitized.is_empty() {
+                return Err(anyhow!("設定エラー: 'run_name' にはディレクトリ名として使える文字を含める必要があります (現在: {:?})", run_name));
+            }
+            config.run_name = Some(sanitized);
+        }
+
+        // JSONポインタは空文字列 (ドキュメント全体) か '/' で始まる必要がある
+        if let Some(pointer) = &config.error_json_pointer
+            && !pointer.is_empty() && !pointer.starts_with('/')
+        {
+            return Err(anyhow!("設定エラー: 'error_json_pointer' は '/' で始まるJSONポインタである必要があります (現在: {})", pointer));
+        }
+
         Ok(config)
     }
-}
\ No newline at end of file
+}
+
+/// cron 式を解釈する。秒のない5項目 ("分 時 日 月 曜日") の場合は0秒として扱う
+/// 6項目 (先頭が秒) と7項目 (末尾が年) の式はそのまま解釈する
+pub fn parse_cron(expression: &str) -> Result<Schedule> {
+    let expression = expression.trim();
+    let normalized = if expression.split_whitespace().count() == 5 {
+        Cow::Owned(format!("0 {}", expression))
+    } else {
+        Cow::Borrowed(expression)
+    };
+    Schedule::from_str(&normalized)
+        .map_err(|e| anyhow!("設定エラー: 'cron' の式 '{}' を解釈できません: {}", expression, e))
+}
+
+/// 文字列中の ${NAME} を lookup で得た値に置き換える ("$$" は "$" そのものとして扱う)
+/// 参照した変数が設定されていない場合は、変数名と設定項目名を含むエラーを返す
+fn expand_env_vars(value: &str, field: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(after_dollar) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = after_dollar;
+        } else if let Some(after_brace) = after.strip_prefix('{') {
+            let end = after_brace.find('}')
+                .ok_or_else(|| anyhow!("設定エラー: '{}' の '${{' が '}}' で閉じられていません。'$' そのものを書く場合は '$$' としてください。", field))?;
+            let name = &after_brace[..end];
+            if name.is_empty() {
+                return Err(anyhow!("設定エラー: '{}' に空の環境変数名 '${{}}' が含まれています。", field));
+            }
+            let env_value = lookup(name)
+                .ok_or_else(|| anyhow!("設定エラー: '{}' で参照している環境変数 '{}' が設定されていません。", field, name))?;
+            expanded.push_str(&env_value);
+            rest = &after_brace[end + 1..];
+        } else {
+            // 後ろに '{' や '$' が続かない '$' はそのまま残す
+            expanded.push('$');
+            rest = after;
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// 複数のエンドポイントを呼び出す場合の、エンドポイントごとの保存先ディレクトリ名
+/// 設定での順番 (1から) とホスト名・パスから作る (例: "1_api.example.com_v1_users")
+pub fn endpoint_dir_name(index: usize, endpoint: &str) -> String {
+    let name = match reqwest::Url::parse(endpoint) {
+        Ok(url) => format!("{}{}", url.host_str().unwrap_or_default(), url.path().trim_end_matches('/')),
+        Err(_) => endpoint.to_string(),
+    };
+    let sanitized: String = sanitize_path_segment(&name).chars().take(64).collect();
+    format!("{}_{}", index + 1, sanitized)
+}
+
+/// ディレクトリ名として安全な文字 (英数字, '-', '_', '.') 以外を '_' に置き換える
+/// 先頭の '.' は取り除く ('..' などで親ディレクトリを指せないようにするため)
+fn sanitize_path_segment(name: &str) -> String {
+    let replaced: String = name.trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    replaced.trim_start_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_matches_config_with_only_required_fields() {
+        let parsed: Config = serde_json::from_str(r#"{"api": "", "on_time": false}"#).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(Config::default()).unwrap());
+    }
+
+    #[test]
+    fn summary_does_not_include_secrets() {
+        let mut config = Config {
+            webhook_url: Some("https://hooks.example.com/services/webhook-token".to_string()),
+            basic_auth: Some(BasicAuth { username: "user".to_string(), password: "secret".to_string() }),
+            ..Config::default()
+        };
+        config.headers.insert("Authorization".to_string(), "Bearer api-token".to_string());
+        config.headers.insert("X-Api-Key".to_string(), "api-key".to_string());
+
+        let summary = config.summary();
+        assert!(summary.contains("headers=[Authorization, X-Api-Key]"));
+        for secret in ["api-token", "api-key", "webhook-token", "secret"] {
+            assert!(!summary.contains(secret), "{}", summary);
+        }
+    }
+
+    #[test]
+    fn clock_mode_accepts_interval_at_upper_bound() {
+        let time = TimeConfig { h: 0, m: 0, s: MAX_INTERVAL_SECS as u32 };
+        assert!(time.validate(false).is_ok());
+        let time = TimeConfig { h: (MAX_INTERVAL_SECS / 3600) as u32, m: 0, s: 0 };
+        assert!(time.validate(false).is_ok());
+    }
+
+    #[test]
+    fn clock_mode_rejects_interval_over_upper_bound() {
+        let time = TimeConfig { h: 0, m: 0, s: MAX_INTERVAL_SECS as u32 + 1 };
+        assert!(time.validate(false).is_err());
+        let time = TimeConfig { h: 1_000_000, m: 0, s: 0 };
+        assert!(time.validate(false).is_err());
+    }
+
+    #[test]
+    fn total_seconds_does_not_overflow_for_max_values() {
+        let time = TimeConfig { h: u32::MAX, m: u32::MAX, s: u32::MAX };
+        assert_eq!(time.total_seconds(), u32::MAX as u64 * 3661);
+        assert!(time.validate(false).is_err());
+        assert!(time.validate(true).is_err());
+    }
+
+    #[test]
+    fn toml_config_with_comments_loads_like_json() {
+        let path = std::env::temp_dir().join(format!("budilnik-config-test-{}.toml", std::process::id()));
+        fs::write(&path, r#"
+# 毎日 9:00 と 18:00 に実行する
+on_time = true
+time = ["09:00:00", "18:00:00"]
+api = "https://api.example.com/data" # エンドポイント
+
+[success_when]
+status = [200, "3xx"]
+"#).unwrap();
+        let config = Config::load_from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert!(config.on_time);
+        assert_eq!(config.times_of_day().len(), 2);
+        assert_eq!(config.api.as_slice(), ["https://api.example.com/data".to_string()]);
+        assert!(config.success_when.unwrap().status_matches(302));
+    }
+
+    #[test]
+    fn interval_parses_units_and_rejects_invalid_syntax() {
+        assert_eq!(parse_interval("90m"), Some(5400));
+        assert_eq!(parse_interval("1h30m"), Some(5400));
+        assert_eq!(parse_interval("1h 30m 15s"), Some(5415));
+        assert_eq!(parse_interval("45s"), Some(45));
+        assert_eq!(parse_interval("2d"), Some(172_800));
+        for invalid in ["", "90", "m", "1.5h", "1x", "every 90m"] {
+            assert_eq!(parse_interval(invalid), None, "{}", invalid);
+        }
+
+        let path = std::env::temp_dir().join(format!("budilnik-config-interval-{}.toml", std::process::id()));
+        fs::write(&path, "on_time = false\napi = \"https://api.example.com\"\ninterval = \"90m\"\n[time]\nh = 0\nm = 5\ns = 0\n").unwrap();
+        let config = Config::load_from_file(path.to_str().unwrap());
+        fs::write(&path, "on_time = false\napi = \"https://api.example.com\"\ninterval = \"90 minutes\"\n").unwrap();
+        let invalid = Config::load_from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let time = config.unwrap().primary_time();
+        assert_eq!((time.h, time.m, time.s), (1, 30, 0));
+        assert!(invalid.unwrap_err().to_string().contains("1h30m"));
+    }
+
+    #[test]
+    fn toml_config_is_validated_like_json() {
+        let path = std::env::temp_dir().join(format!("budilnik-config-invalid-{}.toml", std::process::id()));
+        fs::write(&path, "on_time = true\napi = \"https://api.example.com\"\n[time]\nh = 25\nm = 0\ns = 0\n").unwrap();
+        let config = Config::load_from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn expand_env_vars_replaces_references_and_unescapes_dollar() {
+        let lookup = |name: &str| (name == "API_TOKEN").then(|| "secret".to_string());
+        assert_eq!(expand_env_vars("Bearer ${API_TOKEN}", "headers.Authorization", lookup).unwrap(), "Bearer secret");
+        assert_eq!(expand_env_vars("price=$$5&sort=$name", "api", lookup).unwrap(), "price=$5&sort=$name");
+        assert_eq!(expand_env_vars("$${API_TOKEN}", "api", lookup).unwrap(), "${API_TOKEN}");
+
+        let err = expand_env_vars("https://${API_HOST}/v1", "api", lookup).unwrap_err().to_string();
+        assert!(err.contains("API_HOST"));
+        assert!(expand_env_vars("${API_TOKEN", "api", lookup).is_err());
+    }
+
+    #[test]
+    fn save_raw_bytes_switches_to_binary_and_rejects_json_options() {
+        let path = std::env::temp_dir().join(format!("budilnik-config-raw-{}.toml", std::process::id()));
+        let base = "on_time = true\ntime = [\"09:00:00\"]\napi = \"https://api.example.com/image\"\nsave_raw_bytes = true\n";
+        fs::write(&path, base).unwrap();
+        let config = Config::load_from_file(path.to_str().unwrap());
+        fs::write(&path, format!("{}pretty_json = true\n", base)).unwrap();
+        let with_pretty = Config::load_from_file(path.to_str().unwrap());
+        fs::write(&path, format!("{}require_json = true\n", base)).unwrap();
+        let with_require_json = Config::load_from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.unwrap().response_type, ResponseType::Binary);
+        assert!(with_pretty.is_err());
+        assert!(with_require_json.is_err());
+    }
+
+    #[test]
+    fn cron_accepts_five_and_six_fields_and_rejects_garbage() {
+        assert!(parse_cron("0 9 * * Mon-Fri").is_ok());
+        assert!(parse_cron("30 0 9 * * *").is_ok());
+        let err = parse_cron("every morning").unwrap_err().to_string();
+        assert!(err.contains("every morning"));
+    }
+
+    #[test]
+    fn filename_template_rejects_invalid_format_and_separators() {
+        assert!(validate_filename_template("%H%M%S-{seq}.json").is_ok());
+        assert!(validate_filename_template("").is_err());
+        assert!(validate_filename_template("%Y/%H").is_err());
+        assert!(validate_filename_template("%H-%Q").is_err());
+    }
+}
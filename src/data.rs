@@ -1,44 +1,765 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde::de::IgnoredAny;
+use serde_json::Value;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use tokio::fs::{self, File};
 use tokio::io::AsyncWriteExt; // for AsyncWriteExt trait
-use chrono::Local;
+use chrono::{DateTime, Days, Local, NaiveDate};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write as _;
+
+use crate::config::{endpoint_dir_name, Config, OutputMode, ResponseType};
+
+/// 保存したレスポンスに対応するリクエストのメタデータ
+#[derive(Debug, Serialize)]
+pub struct ResponseMeta {
+    pub ts: String,       // 呼び出し時刻 (RFC3339)
+    pub endpoint: String, // 呼び出したエンドポイント
+    pub status: u16,      // HTTPステータスコード
+    pub latency_ms: u64,  // リクエスト送信から本文受信完了までの時間
+}
+
+/// メタデータを埋め込んだ保存形式 `{ "meta": {...}, "body": <元のJSON> }`
+#[derive(Serialize)]
+struct EmbeddedResponse<'a, B: Serialize + ?Sized> {
+    meta: &'a ResponseMeta,
+    body: &'a B, // 通常は RawValue で元のJSONをそのまま埋め込む (キー順や書式を変えない)
+}
+
+/// jsonl の1行 `{ "time": "HH:MM:SS", "meta": {...}, "body": <元のJSON> }` (meta は embed_meta の場合のみ)
+#[derive(Serialize)]
+struct JsonlRecord<'a> {
+    time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<&'a ResponseMeta>,
+    body: &'a RawValue,
+}
+
+/// jsonl に追記するファイル名 (日付ディレクトリごとに1つ)
+const JSONL_FILENAME: &str = "responses.jsonl";
+
+/// 同時に完了した呼び出しの行が混ざらないよう、jsonl への追記は1つずつ行う
+static JSONL_APPEND_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// manifest.json の1件分 (保存したファイル1つに対応する)
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    pub filename: String, // 保存したファイル名 (manifest.json と同じディレクトリ)
+    pub time: String,     // 保存した時刻 (HH:MM:SS)
+    pub status: u16,      // HTTPステータスコード
+    pub bytes: u64,       // 書き込んだバイト数 (圧縮した場合は圧縮後)
+}
+
+/// 日付ディレクトリの名前の形式 (例: 2025-06-09)
+pub const DATE_DIR_FORMAT: &str = "%Y-%m-%d";
+
+/// 保存したファイルの一覧を記録するファイル名 (保存先ディレクトリごとに1つ)
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// 同時に完了した呼び出しが互いの追加分を上書きしないよう、manifest.json の読み書きは1つずつ行う
+static MANIFEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 古い日付ディレクトリの削除結果
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    pub removed: Vec<PathBuf>,          // 削除したディレクトリ
+    pub failed: Vec<(PathBuf, String)>, // 削除に失敗したディレクトリとその理由
+}
+
+/// 同じ名前のファイルがある場合に試す {seq} の上限
+const MAX_FILENAME_SEQ: u32 = 10_000;
+
+/// レスポンスの保存方法
+#[derive(Debug, Clone, Copy)]
+pub struct SaveOptions<'a> {
+    pub response_type: ResponseType, // 拡張子とJSON固有の処理の有無
+    pub pretty: bool,                // JSONを整形して保存する
+    pub compress: bool,              // gzip で圧縮して保存する
+    pub output_mode: OutputMode,     // ファイルごとに保存するか、responses.jsonl に追記するか
+    pub filename_template: &'a str,  // ファイル名 (拡張子を除く) のテンプレート (strftime と {seq} / {status})
+    pub status: u16,                 // {status} に入れるHTTPステータスコード
+}
+
+impl<'a> SaveOptions<'a> {
+    pub fn from_config(config: &'a Config, status: u16) -> Self {
+        SaveOptions {
+            response_type: config.response_type,
+            pretty: config.pretty_json,
+            compress: config.compress,
+            output_mode: config.output_mode,
+            filename_template: &config.filename_template,
+            status,
+        }
+    }
+}
+
+/// 保存したファイルの情報
+#[derive(Debug)]
+pub struct SavedFile {
+    pub path: PathBuf,            // 保存したレスポンスのパス
+    pub bytes: u64,               // 書き込んだバイト数
+    pub sidecar: Option<PathBuf>, // メタデータをサイドカーに書き出した場合のパス
+    pub pretty_fallback: bool,    // 整形 (pretty_json) を指定されたが、JSONでないためそのまま保存した
+}
+
+/// 一時ファイルに書き出したレスポンス本文 (保存先ディレクトリ内に作成される)
+#[derive(Debug)]
+pub struct TempBody {
+    pub path: PathBuf, // 一時ファイルのパス
+    pub bytes: u64,    // 書き込んだバイト数
+}
 
 /// ディレクトリ構造を管理し、APIレスポンスを保存するモジュール
 pub struct DataManager;
 
 impl DataManager {
+    /// 日付ディレクトリを作成するベースディレクトリ (<output_dir> または <output_dir>/<run_name>)
+    pub fn base_dir(output_dir: &Path, run_name: Option<&str>) -> PathBuf {
+        let mut base_dir = output_dir.to_path_buf();
+        if let Some(run_name) = run_name {
+            base_dir.push(run_name);
+        }
+        base_dir
+    }
+
     /// 初回起動時に必要なディレクトリ構造をセットアップする
-    /// ./jsons/YYYY-MM-DD/ の形式でディレクトリを生成する
-    pub async fn setup_directories() -> Result<PathBuf> {
-        let base_dir = PathBuf::from("./jsons");
+    /// <output_dir>/YYYY-MM-DD/ の形式でディレクトリを生成する (output_dir の既定値は ./jsons)
+    /// run_name が指定された場合は <output_dir>/<run_name>/YYYY-MM-DD/ となる
+    pub async fn setup_directories(output_dir: &Path, run_name: Option<&str>) -> Result<PathBuf> {
+        let base_dir = Self::base_dir(output_dir, run_name);
 
-        // ./jsons ディレクトリが存在するか確認し、なければ作成
+        // ベースディレクトリが存在するか確認し、なければ作成
         if !base_dir.exists() {
-            fs::create_dir_all(&base_dir).await?;
+            Self::create_dir(&base_dir).await?;
         }
 
         // 今日の日付のディレクトリ (例: 2025-06-09) を生成
-        let today_str = Local::now().format("%Y-%m-%d").to_string();
-        let today_dir = base_dir.join(&today_str);
+        Self::setup_date_dir(&base_dir, Local::now().date_naive()).await
+    }
+
+    /// ベースディレクトリ内に指定した日付のディレクトリ (例: 2025-06-09) を用意する
+    /// 実行中に日付が変わった場合にも、新しい日付のディレクトリを作成するために使う
+    pub async fn setup_date_dir(base_dir: &Path, date: NaiveDate) -> Result<PathBuf> {
+        let date_dir = base_dir.join(date.format(DATE_DIR_FORMAT).to_string());
+
+        if !date_dir.exists() {
+            Self::create_dir(&date_dir).await?;
+        }
+
+        Ok(date_dir)
+    }
+
+    /// ディレクトリを作成する (権限がない場合はそれとわかるエラーにする)
+    async fn create_dir(path: &Path) -> Result<()> {
+        fs::create_dir_all(path).await.map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => anyhow!("'{}' を作成する権限がありません", path.display()),
+            _ => anyhow!("'{}' を作成できません: {}", path.display(), e),
+        })
+    }
+
+    /// ベースディレクトリ内の日付ディレクトリ (YYYY-MM-DD) のうち、retention_days 日より古いものを削除する
+    /// 名前が日付として解釈できないディレクトリやファイルには触れない
+    pub async fn cleanup_old_directories(base_dir: &Path, retention_days: u32) -> Result<CleanupReport> {
+        let mut report = CleanupReport::default();
+        if !base_dir.exists() {
+            return Ok(report);
+        }
+
+        let today = Local::now().date_naive();
+        let Some(cutoff) = today.checked_sub_days(Days::new(retention_days.into())) else {
+            return Ok(report);
+        };
+
+        let mut entries = fs::read_dir(base_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            let Some(date) = name.to_str().and_then(|name| NaiveDate::parse_from_str(name, DATE_DIR_FORMAT).ok()) else {
+                continue;
+            };
+            if date >= cutoff {
+                continue;
+            }
+
+            let path = entry.path();
+            match fs::remove_dir_all(&path).await {
+                Ok(()) => report.removed.push(path),
+                Err(e) => report.failed.push((path, e.to_string())),
+            }
+        }
+
+        report.removed.sort();
+        Ok(report)
+    }
+
+    /// 複数のエンドポイントを呼び出す場合に、エンドポイントごとの保存先を今日のディレクトリ内に作成する
+    /// 返すパスの順番は endpoints と同じ
+    pub async fn setup_endpoint_dirs(today_dir: &Path, endpoints: &[String]) -> Result<Vec<PathBuf>> {
+        let mut dirs = Vec::with_capacity(endpoints.len());
+        for (index, endpoint) in endpoints.iter().enumerate() {
+            let dir = today_dir.join(endpoint_dir_name(index, endpoint));
+            if !dir.exists() {
+                Self::create_dir(&dir).await?;
+            }
+            dirs.push(dir);
+        }
+
+        Ok(dirs)
+    }
+
+    /// エラーとみなしたレスポンスの保存先 (今日のディレクトリ内の errors/) を作成して返す
+    pub async fn setup_errors_dir(today_dir: &Path) -> Result<PathBuf> {
+        let errors_dir = today_dir.join("errors");
+
+        if !errors_dir.exists() {
+            Self::create_dir(&errors_dir).await?;
+        }
+
+        Ok(errors_dir)
+    }
 
-        if !today_dir.exists() {
-            fs::create_dir_all(&today_dir).await?;
+    /// APIレスポンスを指定されたディレクトリに保存する
+    /// ファイル名は filename_template に従う (既定は現在の時刻 HH-MM-SS.json。拡張子は response_type に従う)
+    /// pretty が true の場合はJSONを整形して保存する (JSONでない場合はそのまま保存する)
+    /// compress が true の場合は gzip で圧縮し、拡張子に .gz を付ける (例: HHmmss.json.gz)
+    /// output_mode が jsonl の場合は responses.jsonl に1行追記する
+    pub async fn save_api_response(dir: &Path, data: &[u8], options: SaveOptions<'_>) -> Result<SavedFile> {
+        if options.output_mode == OutputMode::Jsonl {
+            return Self::append_jsonl(dir, data, None).await;
         }
+        let (filepath, _) = Self::reserve_filename(dir, &options, options.compress).await?;
+        let (contents, pretty_fallback) = Self::format_body(data, options.response_type, options.pretty);
+        let (filepath, bytes) = Self::write_response_file(filepath, &contents, options.compress).await?;
 
-        Ok(today_dir)
+        Ok(SavedFile { path: filepath, bytes, sidecar: None, pretty_fallback })
     }
 
-    /// APIレスポンスのJSONを指定されたディレクトリに保存する
-    /// ファイル名は現在の時刻 (HHmmss.json) となる
-    pub async fn save_api_response(dir: &Path, json_data: &str) -> Result<()> {
-        let filename = Local::now().format("%H-%M-%S").to_string();
-        let filepath = dir.join(format!("{}.json", filename));
+    /// メタデータ付きでAPIレスポンスを保存する
+    /// 本文が有効なJSONであれば `{ "meta", "body" }` の形に包んで1ファイルに保存する
+    /// JSONでない場合 (response_type が json 以外の場合を含む) は本文をそのまま保存し、
+    /// メタデータを HHmmss.meta.json (サイドカー) に書き出す
+    /// pretty が true の場合は本文を含めて全体を整形して保存する (サイドカーは圧縮しない)
+    /// output_mode が jsonl の場合はメタデータを含めて responses.jsonl に1行追記する
+    pub async fn save_api_response_with_meta(dir: &Path, data: &[u8], options: SaveOptions<'_>, meta: &ResponseMeta) -> Result<SavedFile> {
+        if options.output_mode == OutputMode::Jsonl {
+            return Self::append_jsonl(dir, data, Some(meta)).await;
+        }
+        let (filepath, filename) = Self::reserve_filename(dir, &options, options.compress).await?;
+
+        if options.response_type.is_json()
+            && let Ok(body) = serde_json::from_slice::<&RawValue>(data)
+        {
+            let embedded = if options.pretty {
+                let body: Value = serde_json::from_str(body.get())?;
+                serde_json::to_string_pretty(&EmbeddedResponse { meta, body: &body })?
+            } else {
+                serde_json::to_string(&EmbeddedResponse { meta, body })?
+            };
+            let (filepath, bytes) = Self::write_response_file(filepath, embedded.as_bytes(), options.compress).await?;
+            return Ok(SavedFile { path: filepath, bytes, sidecar: None, pretty_fallback: false });
+        }
+
+        // 埋め込みできない場合はサイドカーファイルにフォールバック
+        let (filepath, bytes) = Self::write_response_file(filepath, data, options.compress).await?;
+        let sidecar_path = Self::write_sidecar(dir, &filename, meta).await?;
+        Ok(SavedFile {
+            path: filepath,
+            bytes,
+            sidecar: Some(sidecar_path),
+            pretty_fallback: options.pretty && options.response_type.is_json(),
+        })
+    }
+
+    /// responses.jsonl にレスポンスを1行で追記する (pretty_json は無視する)
+    /// 本文がJSONでない場合は文字列として埋め込む
+    async fn append_jsonl(dir: &Path, data: &[u8], meta: Option<&ResponseMeta>) -> Result<SavedFile> {
+        let body = match serde_json::from_slice::<&RawValue>(data) {
+            // JSONの文字列中の改行は必ずエスケープされているため、生の改行は空白として取り除いてよい
+            Ok(body) => RawValue::from_string(body.get().replace(['\n', '\r'], ""))?,
+            Err(_) => serde_json::value::to_raw_value(&String::from_utf8_lossy(data))?,
+        };
+        let record = JsonlRecord { time: Local::now().format("%H:%M:%S").to_string(), meta, body: &body };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let filepath = dir.join(JSONL_FILENAME);
+        let _guard = JSONL_APPEND_LOCK.lock().await;
+        let result: std::io::Result<()> = async {
+            let mut file = fs::OpenOptions::new().create(true).append(true).open(&filepath).await?;
+            file.write_all(&line).await?;
+            file.flush().await
+        }.await;
+        result.map_err(|e| anyhow!("'{}' に追記できません: {}", filepath.display(), e))?;
+
+        Ok(SavedFile { path: filepath, bytes: line.len() as u64, sidecar: None, pretty_fallback: false })
+    }
 
-        // ファイルにJSONデータを書き込む
-        let mut file = File::create(&filepath).await?;
-        file.write_all(json_data.as_bytes()).await?;
+    /// 保存したファイルの情報を dir の manifest.json の末尾に追加する
+    /// 既存の manifest.json (同じ日に以前起動したときのものを含む) があれば、その内容に追加する
+    /// 一時ファイルに書いてからリネームするため、途中で切れた manifest.json は残らない
+    pub async fn append_manifest(dir: &Path, entry: ManifestEntry) -> Result<()> {
+        let filepath = dir.join(MANIFEST_FILENAME);
+        let _guard = MANIFEST_LOCK.lock().await;
+        let mut entries: Vec<ManifestEntry> = match fs::read(&filepath).await {
+            Ok(content) => serde_json::from_slice(&content)
+                .map_err(|e| anyhow!("'{}' を読み込めません: {}", filepath.display(), e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        entries.push(entry);
 
+        let temp_path = dir.join(format!(".{}.part", MANIFEST_FILENAME));
+        Self::write_file(&temp_path, &serde_json::to_vec_pretty(&entries)?).await?;
+        Self::rename_file(&temp_path, &filepath).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// レスポンスを書き込み、実際のパスと書き込んだバイト数を返す
+    /// compress が true の場合はメモリ上で gzip に圧縮してから .gz を付けたパスに書き込む
+    /// (圧縮が完了してから書き込むため、途中で切れた .gz ファイルは残らない)
+    async fn write_response_file(filepath: PathBuf, contents: &[u8], compress: bool) -> Result<(PathBuf, u64)> {
+        if !compress {
+            Self::write_file(&filepath, contents).await?;
+            return Ok((filepath, contents.len() as u64));
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents)?;
+        let compressed = encoder.finish()?;
+
+        let mut gz_path = filepath.into_os_string();
+        gz_path.push(".gz");
+        let gz_path = PathBuf::from(gz_path);
+        Self::write_file(&gz_path, &compressed).await?;
+        Ok((gz_path, compressed.len() as u64))
+    }
+
+    /// 保存したレスポンス (とサイドカー) を backup_dir にも同じファイル名で書き出し、バックアップ先のパスを返す
+    /// output_dir からの相対パス (日付ディレクトリなど) を保ったまま backup_dir の下にコピーする
+    /// jsonl の場合は追記後の responses.jsonl 全体をコピーする
+    pub async fn copy_to_backup(saved: &SavedFile, output_dir: &Path, backup_dir: &Path) -> Result<PathBuf> {
+        let backup_path = Self::backup_path(&saved.path, output_dir, backup_dir);
+        // 追記の途中の responses.jsonl をコピーしないよう、追記と同じロックを取る
+        let _guard = match saved.path.file_name() {
+            Some(name) if name == JSONL_FILENAME => Some(JSONL_APPEND_LOCK.lock().await),
+            _ => None,
+        };
+        Self::copy_file(&saved.path, &backup_path).await?;
+        if let Some(sidecar) = &saved.sidecar {
+            Self::copy_file(sidecar, &Self::backup_path(sidecar, output_dir, backup_dir)).await?;
+        }
+
+        Ok(backup_path)
+    }
+
+    /// output_dir 内のパスに対応する backup_dir 内のパス
+    /// (設定の再読み込みで output_dir が変わった場合など、output_dir の外にあればファイル名だけを使う)
+    fn backup_path(path: &Path, output_dir: &Path, backup_dir: &Path) -> PathBuf {
+        let relative = path.strip_prefix(output_dir).unwrap_or_else(|_| Path::new(path.file_name().unwrap_or_default()));
+        backup_dir.join(relative)
+    }
+
+    /// ファイルを一時ファイルにコピーしてからリネームする (コピー先のディレクトリがなければ作成する)
+    /// ネットワーク越しのディレクトリでも、途中で切れたファイルを残さないように
+    async fn copy_file(from: &Path, to: &Path) -> Result<()> {
+        let dir = to.parent().unwrap_or(Path::new("."));
+        if !dir.exists() {
+            Self::create_dir(dir).await?;
+        }
+        let temp_path = dir.join(format!(".{}.part", to.file_name().unwrap_or_default().to_string_lossy()));
+        if let Err(e) = fs::copy(from, &temp_path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(anyhow!("'{}' に書き込めません: {}", temp_path.display(), e));
+        }
+        Self::rename_file(&temp_path, to).await
+    }
+
+    /// 大きなレスポンスを書き出すための一時ファイルを保存先ディレクトリ内に作成する
+    /// (同じディレクトリに置くことで、最終的なファイル名へのリネームをアトミックに行える)
+    pub async fn create_temp_file(dir: &Path) -> Result<(PathBuf, File)> {
+        let temp_name = format!(".{}-{}.part", Local::now().format("%H-%M-%S%.6f"), std::process::id());
+        let temp_path = dir.join(temp_name);
+        let file = File::create(&temp_path).await
+            .map_err(|e| anyhow!("'{}' を作成できません: {}", temp_path.display(), e))?;
+
+        Ok((temp_path, file))
+    }
+
+    /// 一時ファイルに書き出したレスポンスを必要に応じて加工し、最終的なファイル名にリネームする
+    /// meta が指定された場合は、本文をメモリに展開せずにJSONとして検証してからメタデータで包む
+    /// (JSONでない場合は save_api_response_with_meta と同様にサイドカーにフォールバックする)
+    pub async fn save_streamed_response(temp: TempBody, options: SaveOptions<'_>, meta: Option<&ResponseMeta>) -> Result<SavedFile> {
+        let result = Self::finish_streamed_response(&temp, options, meta).await;
+        if result.is_err() {
+            // 失敗した場合は一時ファイルを残さない
+            let _ = fs::remove_file(&temp.path).await;
+        }
+        result
+    }
+
+    async fn finish_streamed_response(temp: &TempBody, options: SaveOptions<'_>, meta: Option<&ResponseMeta>) -> Result<SavedFile> {
+        let dir = temp.path.parent().unwrap_or(Path::new("."));
+        let response_type = options.response_type;
+        let (filepath, filename) = Self::reserve_filename(dir, &options, options.compress).await?;
+
+        let Some(meta) = meta else {
+            let (path, bytes) = Self::place_temp_file(&temp.path, filepath, temp.bytes, options.compress).await?;
+            return Ok(SavedFile { path, bytes, sidecar: None, pretty_fallback: false });
+        };
+
+        if !response_type.is_json() || !Self::is_valid_json_file(&temp.path).await {
+            let (path, bytes) = Self::place_temp_file(&temp.path, filepath, temp.bytes, options.compress).await?;
+            let sidecar_path = Self::write_sidecar(dir, &filename, meta).await?;
+            return Ok(SavedFile { path, bytes, sidecar: Some(sidecar_path), pretty_fallback: false });
+        }
+
+        // `{"meta":...,"body":` + 元の本文 + `}` を別の一時ファイルに書き出してからリネームする
+        let prefix = format!("{{\"meta\":{},\"body\":", serde_json::to_string(meta)?);
+        let (wrapped_path, mut wrapped) = Self::create_temp_file(dir).await?;
+        let copy_result: Result<()> = async {
+            wrapped.write_all(prefix.as_bytes()).await?;
+            let mut body = File::open(&temp.path).await?;
+            tokio::io::copy(&mut body, &mut wrapped).await?;
+            wrapped.write_all(b"}").await?;
+            wrapped.flush().await?;
+            Ok(())
+        }.await;
+        if let Err(e) = copy_result {
+            let _ = fs::remove_file(&wrapped_path).await;
+            return Err(e);
+        }
+
+        let placed = Self::place_temp_file(&wrapped_path, filepath, prefix.len() as u64 + temp.bytes + 1, options.compress).await;
+        let (path, bytes) = match placed {
+            Ok(placed) => placed,
+            Err(e) => {
+                let _ = fs::remove_file(&wrapped_path).await;
+                return Err(e);
+            }
+        };
+        fs::remove_file(&temp.path).await?;
+        Ok(SavedFile { path, bytes, sidecar: None, pretty_fallback: false })
+    }
+
+    /// 一時ファイルを最終的なファイル名にリネームし、実際のパスとバイト数を返す
+    /// compress が true の場合は一時ファイルを gzip で圧縮した別の一時ファイルに書き出してから .gz を付けたパスにリネームし、
+    /// 元の一時ファイルを削除する (本文をメモリに展開しない)
+    async fn place_temp_file(temp_path: &Path, filepath: PathBuf, bytes: u64, compress: bool) -> Result<(PathBuf, u64)> {
+        if !compress {
+            Self::rename_file(temp_path, &filepath).await?;
+            return Ok((filepath, bytes));
+        }
+
+        let gz_temp_path = temp_path.with_extension("gz.part");
+        let (source, target) = (temp_path.to_path_buf(), gz_temp_path.clone());
+        let compress_result = tokio::task::spawn_blocking(move || -> std::io::Result<u64> {
+            let mut source = std::fs::File::open(&source)?;
+            let mut encoder = GzEncoder::new(std::io::BufWriter::new(std::fs::File::create(&target)?), Compression::default());
+            std::io::copy(&mut source, &mut encoder)?;
+            let file = encoder.finish()?.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+            Ok(file.metadata()?.len())
+        }).await;
+        let compressed_bytes = match compress_result {
+            Ok(Ok(compressed_bytes)) => compressed_bytes,
+            Ok(Err(e)) => {
+                let _ = fs::remove_file(&gz_temp_path).await;
+                return Err(anyhow!("'{}' を圧縮できません: {}", gz_temp_path.display(), e));
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&gz_temp_path).await;
+                return Err(anyhow!("圧縮のタスクが失敗しました: {}", e));
+            }
+        };
+
+        let mut gz_path = filepath.into_os_string();
+        gz_path.push(".gz");
+        let gz_path = PathBuf::from(gz_path);
+        if let Err(e) = Self::rename_file(&gz_temp_path, &gz_path).await {
+            let _ = fs::remove_file(&gz_temp_path).await;
+            return Err(e);
+        }
+        fs::remove_file(temp_path).await?;
+        Ok((gz_path, compressed_bytes))
+    }
+
+    /// pretty が有効でJSONとして解釈できる場合は整形した本文を返す
+    /// 解釈できない場合は元の本文と、整形できなかったことを示す true を返す
+    fn format_body(data: &[u8], response_type: ResponseType, pretty: bool) -> (Cow<'_, [u8]>, bool) {
+        if !pretty || !response_type.is_json() {
+            return (Cow::Borrowed(data), false);
+        }
+        match serde_json::from_slice::<Value>(data).and_then(|value| serde_json::to_vec_pretty(&value)) {
+            Ok(formatted) => (Cow::Owned(formatted), false),
+            Err(_) => (Cow::Borrowed(data), true),
+        }
+    }
+
+    /// 保存しないことにした一時ファイルを削除する
+    pub async fn discard_temp_file(temp: TempBody) {
+        let _ = fs::remove_file(&temp.path).await;
+    }
+
+    /// ファイルの内容が妥当なJSONか、値をメモリに保持せずに検証する
+    pub async fn is_valid_json_file(path: &Path) -> bool {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            std::fs::File::open(&path)
+                .map(|file| serde_json::from_reader::<_, IgnoredAny>(std::io::BufReader::new(file)).is_ok())
+                .unwrap_or(false)
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    /// メタデータを HHmmss.meta.json (サイドカー) に書き出す
+    async fn write_sidecar(dir: &Path, filename: &str, meta: &ResponseMeta) -> Result<PathBuf> {
+        let sidecar_path = dir.join(format!("{}.meta.json", filename));
+        let meta_json = serde_json::to_string_pretty(meta)?;
+        Self::write_file(&sidecar_path, meta_json.as_bytes()).await?;
+
+        Ok(sidecar_path)
+    }
+
+    /// レスポンスヘッダーを保存したファイルの隣に HHmmss.headers.txt として書き出す
+    /// 保存したファイル名から .gz と拡張子 (extension) だけを取り除くため、ファイル名に '.' を含むテンプレートでも重ならない
+    /// responses.jsonl に追記した場合は、呼び出しごとに空行で区切って responses.headers.txt に追記する
+    /// headers は1行に1つの `名前: 値` (同じ名前のヘッダーが複数ある場合はそれぞれ1行)
+    pub async fn save_response_headers(saved_path: &Path, extension: &str, headers: &str) -> Result<PathBuf> {
+        let dir = saved_path.parent().unwrap_or(Path::new("."));
+        let file_name = saved_path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name == JSONL_FILENAME {
+            let headers_path = dir.join("responses.headers.txt");
+            let _guard = JSONL_APPEND_LOCK.lock().await;
+            let result: std::io::Result<()> = async {
+                let mut file = fs::OpenOptions::new().create(true).append(true).open(&headers_path).await?;
+                file.write_all(format!("{}\n", headers).as_bytes()).await?;
+                file.flush().await
+            }.await;
+            result.map_err(|e| anyhow!("'{}' に追記できません: {}", headers_path.display(), e))?;
+            return Ok(headers_path);
+        }
+
+        let name = file_name.strip_suffix(".gz").unwrap_or(&file_name);
+        let stem = name.strip_suffix(&format!(".{}", extension)).unwrap_or(name);
+        let headers_path = dir.join(format!("{}.headers.txt", stem));
+        Self::write_file(&headers_path, headers.as_bytes()).await?;
+
+        Ok(headers_path)
+    }
+
+    /// 保存先のパスとファイル名 (拡張子を除く) を決め、空のファイルを作成して名前を確保する
+    /// (同じ秒に複数の保存が重なっても、互いのファイルを上書きしないように)
+    /// 同じ名前のファイルがあれば {seq} を1つずつ増やす。テンプレートに {seq} がない場合は末尾に -2, -3, ... を付ける
+    /// compress が true の場合は .gz を付けたパスを確保し、返すパスは .gz を付ける前のもの (write_response_file に渡す)
+    async fn reserve_filename(dir: &Path, options: &SaveOptions<'_>, compress: bool) -> Result<(PathBuf, String)> {
+        let extension = options.response_type.extension();
+        // テンプレートに拡張子を書いた場合は取り除く (拡張子は response_type に従って付ける)
+        let template = options.filename_template.strip_suffix(&format!(".{}", extension)).unwrap_or(options.filename_template);
+        let has_seq = template.contains("{seq}");
+        let now = Local::now();
+
+        for seq in 1..=MAX_FILENAME_SEQ {
+            let mut filename = expand_filename_template(template, now, options.status, seq);
+            if !has_seq && seq > 1 {
+                filename = format!("{}-{}", filename, seq);
+            }
+            let filepath = dir.join(format!("{}.{}", filename, extension));
+            let reserved_path = if compress { dir.join(format!("{}.{}.gz", filename, extension)) } else { filepath.clone() };
+            match fs::OpenOptions::new().write(true).create_new(true).open(&reserved_path).await {
+                Ok(_) => return Ok((filepath, filename)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(anyhow!("'{}' を作成できません: {}", reserved_path.display(), e)),
+            }
+        }
+        Err(anyhow!("'{}' に空いているファイル名が見つかりません (filename_template: {})", dir.display(), options.filename_template))
+    }
+
+    /// ファイルの名前を変更する (エラーには変更先のパスを含める)
+    async fn rename_file(from: &Path, to: &Path) -> Result<()> {
+        fs::rename(from, to).await
+            .map_err(|e| anyhow!("'{}' に保存できません: {}", to.display(), e))
+    }
+
+    /// ファイルにデータを書き込む (失敗した場合はディスクの空き不足や権限の問題を調べやすいよう、エラーにパスを含める)
+    async fn write_file(filepath: &Path, data: &[u8]) -> Result<()> {
+        let result: std::io::Result<()> = async {
+            let mut file = File::create(filepath).await?;
+            file.write_all(data).await?;
+            file.flush().await
+        }.await;
+        result.map_err(|e| anyhow!("'{}' に書き込めません: {}", filepath.display(), e))
+    }
+}
+
+/// ファイル名のテンプレートを展開する (strftime の書式の後に {status} と {seq} を置き換える)
+fn expand_filename_template(template: &str, now: DateTime<Local>, status: u16, seq: u32) -> String {
+    now.format(template).to_string()
+        .replace("{status}", &status.to_string())
+        .replace("{seq}", &seq.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn concurrent_manifest_appends_keep_every_entry() {
+        let dir = std::env::temp_dir().join(format!("budilnik-manifest-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let entry = |i: u16| ManifestEntry { filename: format!("{}.json", i), time: "09:00:00".to_string(), status: 200, bytes: i as u64 };
+
+        DataManager::append_manifest(&dir, entry(0)).await.unwrap();
+        let tasks: Vec<_> = (1..=4).map(|i| {
+            let dir = dir.clone();
+            tokio::spawn(async move { DataManager::append_manifest(&dir, entry(i)).await })
+        }).collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let entries: Vec<ManifestEntry> = serde_json::from_slice(&fs::read(dir.join(MANIFEST_FILENAME)).await.unwrap()).unwrap();
+        fs::remove_dir_all(&dir).await.unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], entry(0));
+    }
+
+    #[tokio::test]
+    async fn setup_date_dir_creates_next_day_next_to_previous_day() {
+        let base_dir = std::env::temp_dir().join(format!("budilnik-date-dir-test-{}", std::process::id()));
+        let day = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let today_dir = DataManager::setup_date_dir(&base_dir, day).await.unwrap();
+        let next_dir = DataManager::setup_date_dir(&base_dir, day.succ_opt().unwrap()).await.unwrap();
+        let (today_exists, next_exists) = (today_dir.is_dir(), next_dir.is_dir());
+        fs::remove_dir_all(&base_dir).await.unwrap();
+
+        assert!(today_exists && next_exists);
+        assert_eq!(today_dir, base_dir.join("2024-05-01"));
+        assert_eq!(next_dir, base_dir.join("2024-05-02"));
+    }
+
+    #[tokio::test]
+    async fn filename_template_keeps_saves_in_the_same_second_apart() {
+        let dir = std::env::temp_dir().join(format!("budilnik-filename-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let options = |filename_template| SaveOptions {
+            response_type: ResponseType::Json,
+            pretty: false,
+            compress: false,
+            output_mode: OutputMode::Files,
+            filename_template,
+            status: 200,
+        };
+
+        let mut names = Vec::new();
+        for filename_template in ["fixed", "fixed", "{status}-{seq}.json", "{status}-{seq}.json"] {
+            let saved = DataManager::save_api_response(&dir, b"{}", options(filename_template)).await.unwrap();
+            names.push(saved.path.file_name().unwrap().to_string_lossy().into_owned());
+        }
+        fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(names, ["fixed.json", "fixed-2.json", "200-1.json", "200-2.json"]);
+    }
+
+    #[tokio::test]
+    async fn streamed_response_is_compressed_from_temp_file() {
+        let dir = std::env::temp_dir().join(format!("budilnik-stream-gzip-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let body = format!("[{}]", vec!["{\"a\":1}"; 1000].join(","));
+        let (temp_path, mut file) = DataManager::create_temp_file(&dir).await.unwrap();
+        file.write_all(body.as_bytes()).await.unwrap();
+        file.flush().await.unwrap();
+        let options = SaveOptions {
+            response_type: ResponseType::Json,
+            pretty: false,
+            compress: true,
+            output_mode: OutputMode::Files,
+            filename_template: "response",
+            status: 200,
+        };
+
+        let temp = TempBody { path: temp_path.clone(), bytes: body.len() as u64 };
+        let saved = DataManager::save_streamed_response(temp, options, None).await.unwrap();
+        let compressed = fs::read(&saved.path).await.unwrap();
+        let mut decompressed = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(compressed.as_slice()), &mut decompressed).unwrap();
+        let mut leftover = Vec::new();
+        let mut entries = fs::read_dir(&dir).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            leftover.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(saved.path, dir.join("response.json.gz"));
+        assert_eq!(saved.bytes, compressed.len() as u64);
+        assert!(saved.bytes < body.len() as u64);
+        assert_eq!(decompressed, body);
+        // 一時ファイルは残らない
+        assert_eq!(leftover, ["response.json.gz"]);
+    }
+
+    #[tokio::test]
+    async fn response_headers_keep_dotted_filenames_apart() {
+        let dir = std::env::temp_dir().join(format!("budilnik-headers-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+
+        let mut paths = Vec::new();
+        for saved_name in ["2024.05.01-090000.json", "2024.05.01-090001.json.gz", "responses.jsonl", "responses.jsonl"] {
+            paths.push(DataManager::save_response_headers(&dir.join(saved_name), "json", "ETag: \"a\"\n").await.unwrap());
+        }
+        let jsonl_headers = fs::read_to_string(dir.join("responses.headers.txt")).await.unwrap();
+        fs::remove_dir_all(&dir).await.unwrap();
+
+        let names: Vec<_> = paths.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["2024.05.01-090000.headers.txt", "2024.05.01-090001.headers.txt", "responses.headers.txt", "responses.headers.txt"]);
+        // jsonl の場合は上書きせずに追記する
+        assert_eq!(jsonl_headers, "ETag: \"a\"\n\nETag: \"a\"\n\n");
+    }
+
+    #[tokio::test]
+    async fn save_error_includes_target_path() {
+        let dir = std::env::temp_dir().join(format!("budilnik-missing-dir-{}", std::process::id()));
+        let options = SaveOptions {
+            response_type: ResponseType::Json,
+            pretty: false,
+            compress: false,
+            output_mode: OutputMode::Files,
+            filename_template: "response",
+            status: 200,
+        };
+
+        let err = DataManager::save_api_response(&dir, b"{}", options).await.unwrap_err().to_string();
+        assert!(err.contains(&dir.join("response.json").display().to_string()), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn copy_to_backup_keeps_relative_path_and_filename() {
+        let root = std::env::temp_dir().join(format!("budilnik-backup-test-{}", std::process::id()));
+        let (output_dir, backup_dir) = (root.join("jsons"), root.join("backup"));
+        let dir = output_dir.join("2024-05-01");
+        fs::create_dir_all(&dir).await.unwrap();
+        let options = SaveOptions {
+            response_type: ResponseType::Json,
+            pretty: false,
+            compress: false,
+            output_mode: OutputMode::Files,
+            filename_template: "response",
+            status: 200,
+        };
+
+        let saved = DataManager::save_api_response(&dir, b"{\"a\":1}", options).await.unwrap();
+        let backup_path = DataManager::copy_to_backup(&saved, &output_dir, &backup_dir).await.unwrap();
+        let content = fs::read(&backup_path).await.unwrap();
+        fs::remove_dir_all(&root).await.unwrap();
+
+        assert_eq!(backup_path, backup_dir.join("2024-05-01").join("response.json"));
+        assert_eq!(content, b"{\"a\":1}");
+    }
+}
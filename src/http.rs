@@ -1,77 +1,1173 @@
 // src/http.rs
 
-use reqwest::Client;
-use std::path::PathBuf;
-use crate::data::DataManager; // dataモジュールをインポート
-use crate::app::App; // Appの状態を更新するためにインポート
+use reqwest::{Certificate, Client, NoProxy, Proxy, RequestBuilder, Response};
+use reqwest::StatusCode;
+use reqwest::redirect::Policy;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION};
+use std::hash::{BuildHasher, DefaultHasher, Hasher, RandomState};
+use serde::de::IgnoredAny;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::time::sleep;
+use crate::data::{DataManager, ManifestEntry, ResponseMeta, SaveOptions, SavedFile, TempBody, MANIFEST_FILENAME}; // dataモジュールをインポート
+use crate::app::{App, CacheValidators, LogLevel, SaveRecord}; // Appの状態を更新するためにインポート
+use crate::config::{Config, OutputMode, ResponseType};
 use std::sync::{Arc, Mutex}; // Arc<Mutex<App>> を受け取るために必要
 
+/// 再試行の待機時間の基準値 (試行ごとに2倍になる)
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// レート制限のトークン待ちで許容する最大の待機時間 (これを超える場合はスキップする)
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(2);
+
+/// スキーマに合わない本文のログに含めるエラーの最大件数
+const MAX_SCHEMA_ERRORS: usize = 3;
+
+/// レート制限のトークンを取得する
+/// 短時間で補充される場合は待機し、待ち時間が長すぎる場合は false を返す
+async fn acquire_rate_limit_token(app_state: &Arc<Mutex<App>>) -> bool {
+    loop {
+        let wait = {
+            let mut app_guard = app_state.lock().unwrap();
+            let Some(bucket) = app_guard.rate_limiter.as_mut() else {
+                return true; // レート制限が設定されていない
+            };
+            match bucket.try_acquire() {
+                Ok(()) => {
+                    let tokens = bucket.tokens();
+                    app_guard.add_debug_log(format!("レート制限: トークンを取得しました (残り {:.2})", tokens));
+                    return true;
+                }
+                Err(wait) => {
+                    let tokens = bucket.tokens();
+                    app_guard.add_debug_log(format!("レート制限: トークン不足 (残り {:.2}, 補充まで {}ms)", tokens, wait.as_millis()));
+                    wait
+                }
+            }
+        };
+        if wait > MAX_RATE_LIMIT_WAIT {
+            return false;
+        }
+        sleep(wait).await;
+    }
+}
+
+/// レスポンスヘッダーから次回実行までに必要な待機時間を求める
+/// Retry-After (秒数またはHTTP日付) を優先し、なければ残り回数が0のときの X-RateLimit-Reset
+/// (エポック秒または秒数) を使う
+fn rate_limit_backoff(headers: &HeaderMap) -> Option<chrono::Duration> {
+    let now = chrono::Utc::now();
+    let header_str = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    if let Some(retry_after) = header_str("retry-after") {
+        if let Ok(secs) = retry_after.parse::<i64>() {
+            return chrono::Duration::try_seconds(secs).filter(|wait| *wait > chrono::Duration::zero());
+        }
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(retry_after) {
+            let wait = date.with_timezone(&chrono::Utc) - now;
+            return (wait > chrono::Duration::zero()).then_some(wait);
+        }
+    }
+
+    // 残り回数が分かる場合は、使い切ったときだけリセットを待つ
+    if header_str("x-ratelimit-remaining").is_some_and(|remaining| remaining != "0") {
+        return None;
+    }
+    let reset = header_str("x-ratelimit-reset")?.parse::<i64>().ok()?;
+    // 大きな値はエポック秒、小さな値はリセットまでの秒数とみなす
+    let wait = if reset > 1_000_000_000 {
+        chrono::Duration::try_seconds(reset.saturating_sub(now.timestamp()))?
+    } else {
+        chrono::Duration::try_seconds(reset)?
+    };
+    (wait > chrono::Duration::zero()).then_some(wait)
+}
+
+/// レート制限ヘッダーが待機を指示していれば、次回実行を延期する
+/// 429 の場合は Retry-After 等を優先し、なければ rate_limit_backoff_ms にジッターを加えた時間だけ待つ
+fn apply_rate_limit_headers(app_state: &Arc<Mutex<App>>, config: &Config, status: StatusCode, headers: &HeaderMap) {
+    let backoff = rate_limit_backoff(headers);
+    let Ok(mut app_guard) = app_state.lock() else {
+        return;
+    };
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let wait = backoff.unwrap_or_else(|| jittered_backoff(config.rate_limit_backoff_ms));
+        app_guard.rate_limited_count += 1;
+        app_guard.defer_until(wait);
+        let count = app_guard.rate_limited_count;
+        app_guard.set_error(format!(
+            "429 Too Many Requests: {}秒間のバックオフを適用中です (累計 {} 回)",
+            wait.num_seconds(),
+            count
+        ));
+    } else if let Some(wait) = backoff {
+        app_guard.defer_until(wait);
+    }
+}
+
+/// 基準値に 0〜25% のランダムなジッターを加えた待機時間
+/// (複数のインスタンスが同時に再開して再び制限にかかるのを避ける)
+fn jittered_backoff(base_ms: u64) -> chrono::Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let jitter_ms = random % (base_ms / 4 + 1);
+    i64::try_from(base_ms.saturating_add(jitter_ms)).ok()
+        .and_then(chrono::Duration::try_milliseconds)
+        .unwrap_or(chrono::Duration::MAX)
+}
+
+/// 受信したレスポンス本文 (しきい値以下はメモリ上、超えた場合は一時ファイル)
+enum ResponseBody {
+    InMemory(Vec<u8>),
+    TempFile(TempBody),
+    /// extract の JSONPath に一致する値がなかった (保存しない)
+    NoMatch,
+    /// schema_path のスキーマに合わなかった (保存しない。値は合わなかった理由)
+    SchemaMismatch(String),
+}
+
+/// レスポンス本文を読み込む。threshold バイトを超えた時点で、それまでの内容と残りを
+/// 保存先ディレクトリ内の一時ファイルに書き出し、メモリ使用量を抑える
+/// max_bytes を超えた場合はエラーにする (途中まで書き出した一時ファイルは削除する)
+async fn read_body(mut response: Response, threshold: u64, dir: &Path, max_bytes: Option<u64>) -> anyhow::Result<ResponseBody> {
+    check_content_length(&response, max_bytes)?;
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut temp: Option<(TempBody, tokio::fs::File)> = None;
+    let mut received: u64 = 0;
+
+    let result: anyhow::Result<()> = async {
+        while let Some(chunk) = response.chunk().await? {
+            received += chunk.len() as u64;
+            check_received_bytes(received, max_bytes)?;
+            if let Some((temp_body, file)) = temp.as_mut() {
+                file.write_all(&chunk).await?;
+                temp_body.bytes += chunk.len() as u64;
+                continue;
+            }
+            buffer.extend_from_slice(&chunk);
+            if buffer.len() as u64 > threshold {
+                let (path, file) = DataManager::create_temp_file(dir).await?;
+                let (_, file) = temp.insert((TempBody { path, bytes: buffer.len() as u64 }, file));
+                file.write_all(&buffer).await?;
+                buffer = Vec::new(); // 一時ファイルに移したのでメモリを解放
+            }
+        }
+        if let Some((_, file)) = temp.as_mut() {
+            file.flush().await?;
+        }
+        Ok(())
+    }.await;
+
+    match (result, temp) {
+        (Ok(()), Some((temp_body, _))) => Ok(ResponseBody::TempFile(temp_body)),
+        (Ok(()), None) => Ok(ResponseBody::InMemory(buffer)),
+        (Err(e), temp) => {
+            // 途中で失敗した場合は一時ファイルを残さない
+            if let Some((temp_body, _)) = temp {
+                let _ = tokio::fs::remove_file(&temp_body.path).await;
+            }
+            Err(e)
+        }
+    }
+}
+
+/// 本文を max_bytes まで読み込む。超えた時点で受信を中止してエラーにする
+/// (本文はバイト列のまま扱い、stream_threshold_bytes の場合と同じく文字コードは解釈しない)
+async fn read_body_capped(mut response: Response, max_bytes: u64) -> anyhow::Result<ResponseBody> {
+    check_content_length(&response, Some(max_bytes))?;
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        check_received_bytes((buffer.len() + chunk.len()) as u64, Some(max_bytes))?;
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(ResponseBody::InMemory(buffer))
+}
+
+/// Content-Length が max_bytes を超えている場合は、本文を受信する前にエラーにする
+fn check_content_length(response: &Response, max_bytes: Option<u64>) -> anyhow::Result<()> {
+    match response.content_length() {
+        Some(length) => check_received_bytes(length, max_bytes),
+        None => Ok(()),
+    }
+}
+
+fn check_received_bytes(received: u64, max_bytes: Option<u64>) -> anyhow::Result<()> {
+    match max_bytes {
+        Some(max_bytes) if received > max_bytes => Err(anyhow::anyhow!(
+            "レスポンスが max_response_bytes ({} バイト) を超えたため受信を中止しました (保存しません)",
+            max_bytes
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// レスポンス本文をすべてメモリに読み込む
+/// binary はバイト列をそのまま、json / text は文字コードを解釈したテキストとして読み込む
+async fn read_body_in_memory(response: Response, response_type: ResponseType) -> anyhow::Result<ResponseBody> {
+    let body = match response_type {
+        ResponseType::Binary => response.bytes().await?.to_vec(),
+        ResponseType::Json | ResponseType::Text => response.text().await?.into_bytes(),
+    };
+    Ok(ResponseBody::InMemory(body))
+}
+
+/// 設定に従ってHTTPクライアントを作成する (timeout_secs は本文の受信完了までを含む)
+/// headers はすべてのリクエストに付与する。ca_cert_path の証明書は信頼するルート証明書に追加する
+pub fn build_client(config: &Config) -> anyhow::Result<Client> {
+    // follow_redirects が false の場合は 3xx をそのまま受け取る
+    let redirect_policy = if config.follow_redirects {
+        Policy::limited(config.max_redirects)
+    } else {
+        Policy::none()
+    };
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .redirect(redirect_policy)
+        .default_headers(build_headers(config)?)
+        .user_agent(&config.user_agent)
+        .danger_accept_invalid_certs(config.danger_accept_invalid_certs)
+        // 接続元を IPv4 / IPv6 の未指定アドレスにすると、名前解決の結果のうち同じ種類のアドレスだけに接続する
+        .local_address(config.ip_version.local_address());
+    if let Some(ca_cert_path) = &config.ca_cert_path {
+        let pem = std::fs::read(ca_cert_path)
+            .map_err(|e| anyhow::anyhow!("CA証明書 '{}' を読み込めません: {}", ca_cert_path.display(), e))?;
+        builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+    // 設定でプロキシを指定した場合、reqwest は環境変数のプロキシを使わなくなるため、
+    // 指定しなかった方は環境変数から補う (どちらも指定しなければ reqwest が環境変数を使う)
+    if config.http_proxy.is_some() || config.https_proxy.is_some() {
+        if let Some((url, source)) = proxy_setting(config.http_proxy.as_deref(), "http_proxy", &["HTTP_PROXY", "http_proxy"]) {
+            let proxy = Proxy::http(&url)
+                .map_err(|e| anyhow::anyhow!("プロキシ {} のURLが不正です: {}", source, e.without_url()))?;
+            builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+        }
+        if let Some((url, source)) = proxy_setting(config.https_proxy.as_deref(), "https_proxy", &["HTTPS_PROXY", "https_proxy"]) {
+            let proxy = Proxy::https(&url)
+                .map_err(|e| anyhow::anyhow!("プロキシ {} のURLが不正です: {}", source, e.without_url()))?;
+            builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// 使用するプロキシのURLと、その指定元の説明 (エラーメッセージ用) を返す
+/// 設定ファイルの値を優先し、未指定の場合は環境変数を使う。URLには認証情報が含まれることがあるため説明には含めない
+fn proxy_setting(configured: Option<&str>, field: &str, env_names: &[&str]) -> Option<(String, String)> {
+    if let Some(url) = configured {
+        let source = format!("'{}' (設定ファイル。環境変数 {} より優先されます)", field, env_names[0]);
+        return Some((url.to_string(), source));
+    }
+    env_names.iter().find_map(|name| {
+        let url = std::env::var(name).ok().filter(|url| !url.is_empty())?;
+        let source = format!("'{}' (環境変数。設定ファイルの {} が未指定のため使用しています)", name, field);
+        Some((url, source))
+    })
+}
+
+/// 設定の headers を HeaderMap に変換する
+/// 値にはトークンなどが含まれるため、エラーメッセージにはヘッダー名だけを含める
+fn build_headers(config: &Config) -> anyhow::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow::anyhow!("ヘッダー名 '{}' に使用できない文字が含まれています", name))?;
+        let mut header_value = HeaderValue::from_str(value)
+            .map_err(|_| anyhow::anyhow!("ヘッダー '{}' の値に使用できない文字が含まれています", name))?;
+        header_value.set_sensitive(true); // デバッグ出力などで値を表示しない
+        headers.insert(header_name, header_value);
+    }
+    Ok(headers)
+}
+
+/// 設定されたメソッドと本文でリクエストを組み立てる
+/// 前回のレスポンスに ETag / Last-Modified があれば条件付きリクエストにする (変更がなければ 304 が返る)
+fn build_request(client: &Client, config: &Config, endpoint: &str, validators: Option<&CacheValidators>) -> RequestBuilder {
+    let mut request = client.request(config.method.as_reqwest(), endpoint);
+    // クエリパラメータは URL エンコードしてから追加する (エンドポイントに既にあるクエリの後ろに付く)
+    if !config.query.is_empty() {
+        request = request.query(&config.query);
+    }
+    // reqwest が Authorization ヘッダーを sensitive にするため、資格情報はデバッグ出力にも含まれない
+    if let Some(basic_auth) = &config.basic_auth {
+        request = request.basic_auth(&basic_auth.username, Some(&basic_auth.password));
+    }
+    if let Some(validators) = validators {
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    match &config.body {
+        Some(body) => request
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.clone()),
+        None => request,
+    }
+}
+
+/// リクエストを送信し、ネットワークエラーと 5xx の場合は max_retries 回まで指数バックオフ (1秒, 2秒, 4秒, ...) で再試行する
+/// 4xx などはすぐに返す。再試行しても失敗した場合は最後の結果を返す
+async fn send_with_retry(client: &Client, config: &Config, endpoint: &str, app_state: &Arc<Mutex<App>>) -> reqwest::Result<Response> {
+    let validators = app_state.lock().ok().and_then(|app_guard| app_guard.cache_validators.get(endpoint).cloned());
+    let mut attempt = 0;
+    loop {
+        let result = build_request(client, config, endpoint, validators.as_ref()).send().await;
+        let reason = match &result {
+            Ok(response) if response.status().is_server_error() => format!("ステータス {}", response.status()),
+            Ok(_) => return result,
+            Err(e) => request_error_message(e),
+        };
+        if attempt >= config.max_retries {
+            return result;
+        }
+
+        attempt += 1;
+        let delay = RETRY_BASE_DELAY.saturating_mul(1 << (attempt - 1).min(16));
+        if let Ok(mut app_guard) = app_state.lock() {
+            app_guard.add_log(format!(
+                "リトライ {}/{} ({}、{}秒後に再試行します)",
+                attempt, config.max_retries, reason, delay.as_secs()
+            ));
+        }
+        sleep(delay).await;
+
+        // 再試行もレート制限のトークンを消費する
+        if !acquire_rate_limit_token(app_state).await {
+            return result;
+        }
+    }
+}
+
+/// リクエストのエラーをログ用の文字列にする (タイムアウトはそれとわかるようにする)
+fn request_error_message(e: &reqwest::Error) -> String {
+    if e.is_timeout() {
+        "リクエストがタイムアウトしました".to_string()
+    } else {
+        e.to_string()
+    }
+}
+
+/// extract が設定されている場合、本文を JSONPath に一致した値に置き換える
+/// 一致が1つならその値を、複数なら一致した値の配列を保存する。JSONでない本文や一致しない本文は NoMatch にする
+/// 失敗と判定される本文 (エラーエンベロープなど) と、require_json で弾くJSONでない本文は、元の本文のまま返す
+/// 値を抽出した場合は true も返す
+fn extract_body(config: &Config, path: &str, body: Vec<u8>) -> (anyhow::Result<ResponseBody>, bool) {
+    let Ok(value) = serde_json::from_slice::<Value>(&body) else {
+        let body = if config.require_json { ResponseBody::InMemory(body) } else { ResponseBody::NoMatch };
+        return (Ok(body), false);
+    };
+    if body_failure_reason(config, &body).is_some() {
+        return (Ok(ResponseBody::InMemory(body)), false);
+    }
+    let extracted = match jsonpath_lib::select(&value, path) {
+        Ok(matches) if matches.len() == 1 => serde_json::to_vec(matches[0]),
+        Ok(matches) if !matches.is_empty() => serde_json::to_vec(&matches),
+        _ => return (Ok(ResponseBody::NoMatch), false),
+    };
+    match extracted {
+        Ok(extracted) => (Ok(ResponseBody::InMemory(extracted)), true),
+        Err(e) => (Err(e.into()), false),
+    }
+}
+
+/// 本文の読み込みエラーをログ用の文字列にする
+fn body_error_message(e: &anyhow::Error) -> String {
+    e.downcast_ref::<reqwest::Error>()
+        .map_or_else(|| e.to_string(), request_error_message)
+}
+
+/// 保存するレスポンスのメタデータを作成する
+fn build_meta(endpoint: &str, status: u16, started_at: Instant) -> ResponseMeta {
+    ResponseMeta {
+        ts: chrono::Local::now().to_rfc3339(),
+        endpoint: endpoint.to_string(),
+        status,
+        latency_ms: started_at.elapsed().as_millis() as u64,
+    }
+}
+
+/// 設定に応じてレスポンスを保存する (embed_meta が有効ならメタデータを埋め込む)
+async fn save_response(
+    config: &Config,
+    dir: &Path,
+    endpoint: &str,
+    status: u16,
+    started_at: Instant,
+    body: &[u8],
+) -> anyhow::Result<SavedFile> {
+    if config.embed_meta {
+        let meta = build_meta(endpoint, status, started_at);
+        DataManager::save_api_response_with_meta(dir, body, SaveOptions::from_config(config, status), &meta).await
+    } else {
+        DataManager::save_api_response(dir, body, SaveOptions::from_config(config, status)).await
+    }
+}
+
+/// レスポンスヘッダーを `名前: 値` の行にする (同じ名前のヘッダーが複数ある場合はそれぞれ1行)
+fn format_headers(headers: &HeaderMap) -> String {
+    headers.iter()
+        .map(|(name, value)| format!("{}: {}\n", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect()
+}
+
+/// save_headers が有効な場合、保存したレスポンスの隣にヘッダーを書き出す
+/// 失敗しても本文の保存は成功しているため、エラーはログに残すだけにする
+async fn save_headers(app_state: &Arc<Mutex<App>>, config: &Config, saved: &SavedFile, headers: Option<&HeaderMap>) {
+    let Some(headers) = headers else {
+        return;
+    };
+    if let Err(e) = DataManager::save_response_headers(&saved.path, config.response_type.extension(), &format_headers(headers)).await
+        && let Ok(mut app_guard) = app_state.lock()
+    {
+        app_guard.set_error(format!("レスポンスヘッダーの保存に失敗しました: {}", e));
+    }
+}
+
+/// レスポンスの ETag / Last-Modified を取り出す (どちらもなければ None)
+fn cache_validators(headers: &HeaderMap) -> Option<CacheValidators> {
+    let header_text = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).map(str::to_string);
+    let validators = CacheValidators { etag: header_text(ETAG), last_modified: header_text(LAST_MODIFIED) };
+    (validators.etag.is_some() || validators.last_modified.is_some()).then_some(validators)
+}
+
+/// 保存に成功したレスポンスの ETag / Last-Modified を次回の条件付きリクエスト用に記録する
+fn remember_cache_validators(app_state: &Arc<Mutex<App>>, endpoint: &str, validators: Option<CacheValidators>) {
+    if let Some(validators) = validators
+        && let Ok(mut app_guard) = app_state.lock()
+    {
+        app_guard.cache_validators.insert(endpoint.to_string(), validators);
+    }
+}
+
+/// skip_unchanged で前回の本文と比べるためのハッシュ値
+fn body_hash(body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(body);
+    hasher.finish()
+}
+
+/// 本文が同じエンドポイントで最後に保存した本文と同じかどうか
+fn is_unchanged_body(app_state: &Arc<Mutex<App>>, endpoint: &str, body: &[u8]) -> bool {
+    app_state.lock().is_ok_and(|app_guard| app_guard.last_body_hashes.get(endpoint) == Some(&body_hash(body)))
+}
+
+/// 保存した本文のハッシュを次回の比較用に記録する (実行中のみ保持)
+fn remember_body_hash(app_state: &Arc<Mutex<App>>, config: &Config, endpoint: &str, body: &[u8]) {
+    if config.skip_unchanged
+        && let Ok(mut app_guard) = app_state.lock()
+    {
+        app_guard.last_body_hashes.insert(endpoint.to_string(), body_hash(body));
+    }
+}
+
+/// 失敗した呼び出しを再試行対象として記録する (2回目以降の呼び出し用)
+fn record_failed_request(app_state: &Arc<Mutex<App>>, endpoint: &str) {
+    if let Ok(mut app_guard) = app_state.lock() {
+        app_guard.record_failed_request(endpoint, false);
+    }
+}
+
+/// 呼び出しが成功したら再試行対象から外す
+fn clear_failed_request(app_state: &Arc<Mutex<App>>, endpoint: &str) {
+    if let Ok(mut app_guard) = app_state.lock() {
+        app_guard.clear_failed_request(endpoint);
+    }
+}
+
+/// backup_dir が設定されている場合、保存したレスポンスをバックアップ先にも書き出す
+/// バックアップに失敗しても本文の保存は成功しているため、警告をログに残すだけにする
+async fn save_backup(app_state: &Arc<Mutex<App>>, config: &Config, saved: &SavedFile) {
+    let Some(backup_dir) = &config.backup_dir else {
+        return;
+    };
+    if let Err(e) = DataManager::copy_to_backup(saved, &config.output_dir, backup_dir).await
+        && let Ok(mut app_guard) = app_state.lock()
+    {
+        app_guard.add_log_with_level(LogLevel::Warn, format!("バックアップの保存に失敗しました: {}", e));
+    }
+}
+
+/// 保存したファイルを保存ファイル一覧ペインに記録する
+fn record_save(app_state: &Arc<Mutex<App>>, saved: &SavedFile, status: u16) {
+    if let Ok(mut app_guard) = app_state.lock() {
+        app_guard.last_saved_path = Some(saved.path.clone());
+        app_guard.add_recent_save(SaveRecord {
+            filename: saved.path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            bytes: saved.bytes,
+            status,
+            ts: chrono::Local::now().format("%H:%M:%S").to_string(),
+        });
+    }
+}
+
+/// 保存したファイルを同じディレクトリの manifest.json に記録する (jsonl では1つのファイルに追記するため記録しない)
+/// 記録に失敗してもレスポンスの保存は成功しているため、警告をログに残すだけにする
+async fn record_manifest(app_state: &Arc<Mutex<App>>, config: &Config, saved: &SavedFile, status: u16) {
+    if config.output_mode == OutputMode::Jsonl {
+        return;
+    }
+    let entry = ManifestEntry {
+        filename: saved.path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        time: chrono::Local::now().format("%H:%M:%S").to_string(),
+        status,
+        bytes: saved.bytes,
+    };
+    let dir = saved.path.parent().unwrap_or(Path::new("."));
+    if let Err(e) = DataManager::append_manifest(dir, entry).await
+        && let Ok(mut app_guard) = app_state.lock()
+    {
+        app_guard.add_log_with_level(LogLevel::Warn, format!("{} の更新に失敗しました: {}", MANIFEST_FILENAME, e));
+    }
+}
+
+/// error_json_pointer が指す値がレスポンスに存在し、真とみなせる場合はその値を返す
+/// (null / false / 0 / 空文字列は偽とみなす。JSONでないレスポンスはエラー扱いしない)
+fn find_error_envelope(config: &Config, body: &[u8]) -> Option<Value> {
+    if !config.response_type.is_json() {
+        return None;
+    }
+    let pointer = config.error_json_pointer.as_deref()?;
+    let body: Value = serde_json::from_slice(body).ok()?;
+    let value = body.pointer(pointer)?;
+
+    let is_truthy = match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    };
+    is_truthy.then(|| value.clone())
+}
+
+/// 本文を検査し、失敗とみなす場合はその理由を返す
+/// (error_json_pointer のエラーエンベロープと success_when の本文条件を評価する)
+fn body_failure_reason(config: &Config, body: &[u8]) -> Option<String> {
+    if let Some(error_value) = find_error_envelope(config, body) {
+        return Some(format!("レスポンスにエラーが含まれています ({})", error_value));
+    }
+    let condition = config.success_when.as_ref()?;
+    condition.check_body(body).err().map(|reason| format!("成功条件を満たしません: {}", reason))
+}
+
+/// schema_path が設定されている場合、本文がスキーマに合わない理由を返す (最初の MAX_SCHEMA_ERRORS 件)
+/// エラーエンベロープなど本文の判定で失敗となる本文は、これまでどおり errors/ に保存するため検証しない
+fn schema_violation(config: &Config, body: &[u8]) -> Option<String> {
+    let schema = config.schema.as_ref()?;
+    if body_failure_reason(config, body).is_some() {
+        return None;
+    }
+    let Ok(value) = serde_json::from_slice::<Value>(body) else {
+        return Some("レスポンスがJSONではないため、スキーマで検証できません".to_string());
+    };
+    let errors: Vec<String> = schema.iter_errors(&value)
+        .take(MAX_SCHEMA_ERRORS)
+        .map(|e| {
+            let path = e.instance_path.to_string();
+            format!("{}: {}", if path.is_empty() { "/" } else { &path }, e)
+        })
+        .collect();
+    (!errors.is_empty()).then(|| format!("スキーマに合いません ({})", errors.join("; ")))
+}
+
+/// HTTPステータスが成功とみなせるか (success_when が未設定の場合は 2xx のみ)
+fn is_success_status(config: &Config, status: StatusCode) -> bool {
+    match &config.success_when {
+        Some(condition) if config.expected_statuses.is_empty() => condition.status_matches(status.as_u16()),
+        _ if !config.expected_statuses.is_empty() => config.expected_statuses.iter().any(|pattern| pattern.matches(status.as_u16())),
+        _ => status.is_success(),
+    }
+}
+
+/// API呼び出しの結果 (ログの文言は受け取った側で組み立てる)
+#[derive(Debug)]
+pub enum ApiCallOutcome {
+    /// 成功 (初回呼び出しなど、レスポンスを保存しない場合は saved が None)
+    Success { status: u16, saved: Option<SavedFile> },
+    /// ステータスは成功だが本文が条件を満たさなかった (errors/ に保存した場合は saved にそのパス)
+    Rejected { status: u16, reason: String, saved: Option<PathBuf> },
+    /// 成功とみなせないステータスを受け取った
+    HttpError { status: u16 },
+    /// リクエストを送信できなかった (接続失敗・タイムアウトなど)
+    NetworkError { msg: String },
+    /// レスポンス本文の受信に失敗した
+    ReadError { status: u16, msg: String },
+    /// レスポンスの保存に失敗した
+    SaveError { status: u16, msg: String },
+    /// 条件付きリクエストに 304 が返った (前回から変更がないため保存しない)
+    NotModified,
+    /// 204 No Content が返った (本文がないため保存しない)
+    NoContent,
+    /// skip_unchanged が有効で、本文が前回保存したものと同じだった (保存しない)
+    Unchanged { status: u16 },
+    /// extract の JSONPath に一致する値がなかった (保存しない)
+    NoMatch { status: u16 },
+    /// follow_redirects が false の場合に 3xx を受け取った (リダイレクト先は Location ヘッダー)
+    Redirected { status: u16, location: Option<String> },
+    /// レート制限などによりリクエストを送信しなかった
+    Skipped { reason: String },
+}
+
+impl ApiCallOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            ApiCallOutcome::Success { .. }
+                | ApiCallOutcome::NotModified
+                | ApiCallOutcome::NoContent
+                | ApiCallOutcome::Unchanged { .. }
+                | ApiCallOutcome::NoMatch { .. }
+                | ApiCallOutcome::Redirected { .. }
+        )
+    }
+
+    /// 呼び出しは成功したが、警告としてログに残す結果
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ApiCallOutcome::NoMatch { .. })
+    }
+
+    /// リクエストを送信しなかった場合は呼び出し回数に数えない
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, ApiCallOutcome::Skipped { .. })
+    }
+
+    /// 受け取ったステータスコード (レスポンスを受け取れなかった場合は None)
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ApiCallOutcome::Success { status, .. }
+            | ApiCallOutcome::Rejected { status, .. }
+            | ApiCallOutcome::HttpError { status }
+            | ApiCallOutcome::ReadError { status, .. }
+            | ApiCallOutcome::SaveError { status, .. }
+            | ApiCallOutcome::Unchanged { status }
+            | ApiCallOutcome::NoMatch { status }
+            | ApiCallOutcome::Redirected { status, .. } => Some(*status),
+            ApiCallOutcome::NotModified => Some(304),
+            ApiCallOutcome::NoContent => Some(204),
+            ApiCallOutcome::NetworkError { .. } | ApiCallOutcome::Skipped { .. } => None,
+        }
+    }
+}
+
+/// 1回の呼び出しにかかった時間 (再試行・本文の受信・保存を含む) と受信した本文のサイズ
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CallMetrics {
+    pub elapsed: Duration,
+    pub body_bytes: Option<u64>, // 初回呼び出しなど、本文を受信しなかった場合は None
+}
+
 /// API呼び出しのロジックをカプセル化する
 ///
-/// is_first_call: API呼び出しが初回かどうか (初回はステータスのみ、次回以降はJSON保存)
+/// client: main で1度だけ作成し、すべての呼び出しで共有するHTTPクライアント
+/// is_first_call: API呼び出しが初回かどうか (初回はステータスのみ、次回以降はJSON保存。save_first_response なら初回も保存)
 /// endpoint: APIのエンドポイントURL
 /// today_json_dir: JSON保存先ディレクトリのパス (Option<PathBuf> で None の場合も考慮)
 /// app_state: Appの状態を更新するための Arc<Mutex<App>>
+/// config: 保存形式などのオプションを参照するための設定
 pub async fn fetch_api_data(
+    client: Client,
     is_first_call: bool,
     endpoint: String,
     today_json_dir: Option<PathBuf>,
     app_state: Arc<Mutex<App>>, // Appの状態を更新するために追加
-) -> String {
-    let client = Client::new();
-    let log_message: String; // ここを修正: 初期化を省略し、型のみを宣言
+    config: Arc<Config>,
+) -> (ApiCallOutcome, CallMetrics) {
+    // ドライランではリクエストを送信せず、呼び出す予定のエンドポイントだけをログに残す
+    // スケジュールの確認ができるよう、初回フラグは通常の呼び出しと同じく下ろす
+    if config.dry_run {
+        if let Ok(mut app_guard) = app_state.lock() {
+            app_guard.is_first_api_call = false;
+        }
+        return (ApiCallOutcome::Skipped { reason: format!("[DRY-RUN] would fetch {}", endpoint) }, CallMetrics::default());
+    }
+
+    // 同じエンドポイントへの前回のリクエストから min_interval_secs が経過していなければ送信しない
+    if !app_state.lock().unwrap().min_interval_elapsed(&endpoint, Instant::now()) {
+        return (ApiCallOutcome::Skipped { reason: format!("レート制限によりスキップ: {}", endpoint) }, CallMetrics::default());
+    }
+
+    // すべてのリクエストはレート制限のトークンを取得してから送信する
+    if !acquire_rate_limit_token(&app_state).await {
+        return (ApiCallOutcome::Skipped { reason: "レート制限: トークンが不足しているため、API呼び出しをスキップしました。".to_string() }, CallMetrics::default());
+    }
+    app_state.lock().unwrap().record_request_sent(&endpoint, Instant::now());
 
-    if is_first_call {
+    let started_at = Instant::now();
+    let mut body_bytes = None; // 本文を受信した場合のみ設定する
+    let outcome = if is_first_call && !config.save_first_response {
         // 初回API呼び出し: HTTPステータスのみ表示
-        match client.get(&endpoint).send().await {
+        match send_with_retry(&client, &config, &endpoint, &app_state).await {
             Ok(response) => {
-                log_message = format!("初回API呼び出し完了 (ステータス: {})", response.status());
-                // Appのis_first_api_callフラグをここでfalseに設定
-                if let Ok(mut app_guard) = app_state.lock() {
-                    app_guard.is_first_api_call = false;
-                    app_guard.set_status_message(format!("初回API呼び出し成功: ステータス {}", response.status()));
+                let status = response.status();
+                apply_rate_limit_headers(&app_state, &config, status, response.headers());
+                // 再試行しても成功のステータスにならなかった場合は失敗として記録し、'R' キーで再試行できるようにする
+                // (follow_redirects が false の場合の 3xx は、2回目以降と同じく失敗にしない)
+                // 成否にかかわらず初回フラグは下ろし、以降はスケジュールどおりに呼び出す
+                if is_success_status(&config, status) || (!config.follow_redirects && status.is_redirection()) {
+                    // Appのis_first_api_callフラグをここでfalseに設定
+                    if let Ok(mut app_guard) = app_state.lock() {
+                        app_guard.is_first_api_call = false;
+                        app_guard.clear_failed_request(&endpoint);
+                        app_guard.set_status_message(format!("初回API呼び出し成功: ステータス {}", status));
+                    }
+                    ApiCallOutcome::Success { status: status.as_u16(), saved: None }
+                } else {
+                    if let Ok(mut app_guard) = app_state.lock() {
+                        app_guard.is_first_api_call = false;
+                        app_guard.record_failed_request(&endpoint, true);
+                        app_guard.set_error(format!("初回API呼び出し失敗: ステータス {}", status));
+                    }
+                    ApiCallOutcome::HttpError { status: status.as_u16() }
                 }
             }
             Err(e) => {
-                log_message = format!("初回API呼び出し失敗: {}", e);
+                let error_message = request_error_message(&e);
                 if let Ok(mut app_guard) = app_state.lock() {
-                    app_guard.set_error(format!("初回API呼び出し失敗: {}", e));
+                    app_guard.record_failed_request(&endpoint, true);
+                    app_guard.set_error(format!("初回API呼び出し失敗: {}", error_message));
                 }
+                ApiCallOutcome::NetworkError { msg: error_message }
             }
         }
     } else {
-        // 2回目以降のAPI呼び出し: JSONを保存
-        match client.get(&endpoint).send().await {
+        // 2回目以降のAPI呼び出し (save_first_response の場合は初回も): JSONを保存
+        match send_with_retry(&client, &config, &endpoint, &app_state).await {
             Ok(response) => {
-                if response.status().is_success() {
-                    match response.text().await {
-                        Ok(json_text) => {
+                let status = response.status();
+                apply_rate_limit_headers(&app_state, &config, status, response.headers());
+                if status == StatusCode::NOT_MODIFIED {
+                    // 前回から変更がないため、同じ内容のファイルは保存しない
+                    clear_failed_request(&app_state, &endpoint);
+                    ApiCallOutcome::NotModified
+                } else if status == StatusCode::NO_CONTENT && is_success_status(&config, status) {
+                    // 204 には本文がないため、空のファイルを保存したり require_json で失敗にしたりしない
+                    clear_failed_request(&app_state, &endpoint);
+                    ApiCallOutcome::NoContent
+                } else if is_success_status(&config, status) {
+                    // 本文の読み込みで response を消費する前にヘッダーを控えておく
+                    let headers = config.save_headers.then(|| response.headers().clone());
+                    let validators = cache_validators(response.headers());
+                    // stream_threshold_bytes が設定されていれば、しきい値を超えた本文は一時ファイルに書き出す
+                    // max_response_bytes が設定されていれば、上限を超えた時点で受信を中止する
+                    let body = match (config.stream_threshold_bytes, today_json_dir.as_deref(), config.max_response_bytes) {
+                        (Some(threshold), Some(dir), max_bytes) => read_body(response, threshold, dir, max_bytes).await,
+                        (_, _, Some(max_bytes)) => read_body_capped(response, max_bytes).await,
+                        _ => read_body_in_memory(response, config.response_type).await,
+                    };
+                    body_bytes = match &body {
+                        Ok(ResponseBody::InMemory(body)) => Some(body.len() as u64),
+                        Ok(ResponseBody::TempFile(temp)) => Some(temp.bytes),
+                        Ok(ResponseBody::NoMatch) | Ok(ResponseBody::SchemaMismatch(_)) | Err(_) => None,
+                    };
+                    // schema_path が設定されていれば、スキーマに合わない本文は保存しない (extract の前に、受信した本文を検証する)
+                    let body = match body {
+                        Ok(ResponseBody::InMemory(body)) => match schema_violation(&config, &body) {
+                            Some(reason) => Ok(ResponseBody::SchemaMismatch(reason)),
+                            None => Ok(ResponseBody::InMemory(body)),
+                        },
+                        body => body,
+                    };
+                    // extract が設定されていれば、一致した値だけを保存する (ログのサイズは受信した本文のもの)
+                    // 抽出した値は元の本文で失敗の判定を済ませているため、もう一度判定しない
+                    let (body, body_extracted) = match (body, &config.extract) {
+                        (Ok(ResponseBody::InMemory(body)), Some(path)) => extract_body(&config, path, body),
+                        (body, _) => (body, false),
+                    };
+                    match body {
+                        // require_json が有効な場合、JSONとして解釈できない本文 (HTMLのエラーページなど) は保存しない
+                        Ok(ResponseBody::InMemory(body)) if config.require_json && serde_json::from_slice::<IgnoredAny>(&body).is_err() => {
+                            record_failed_request(&app_state, &endpoint);
+                            ApiCallOutcome::Rejected { status: status.as_u16(), reason: "レスポンスがJSONではありません".to_string(), saved: None }
+                        }
+                        // skip_unchanged が有効な場合、前回保存した本文と同じ内容はファイルに書き出さない
+                        Ok(ResponseBody::InMemory(body)) if config.skip_unchanged
+                            && (body_extracted || body_failure_reason(&config, &body).is_none())
+                            && is_unchanged_body(&app_state, &endpoint, &body) =>
+                        {
+                            clear_failed_request(&app_state, &endpoint);
+                            remember_cache_validators(&app_state, &endpoint, validators);
+                            ApiCallOutcome::Unchanged { status: status.as_u16() }
+                        }
+                        Ok(ResponseBody::InMemory(body)) => {
                             if let Some(dir) = today_json_dir {
-                                match DataManager::save_api_response(&dir, &json_text).await {
-                                    Ok(_) => {
-                                        // JSONファイル名形式の変更に合わせてここも修正
-                                        log_message = format!("API呼び出し成功: JSONを保存しました ({})", chrono::Local::now().format("%H-%M-%S").to_string());
-                                    }
-                                    Err(e) => {
-                                        log_message = format!("API呼び出し成功、JSON保存失敗: {}", e);
+                                // ステータスが成功でも、本文が条件を満たさないレスポンスは失敗として errors/ に保存する
+                                let failure_reason = if body_extracted { None } else { body_failure_reason(&config, &body) };
+                                let save_result = match failure_reason {
+                                    Some(_) => match DataManager::setup_errors_dir(&dir).await {
+                                        Ok(errors_dir) => save_response(&config, &errors_dir, &endpoint, status.as_u16(), started_at, &body).await,
+                                        Err(e) => Err(e),
+                                    },
+                                    None => save_response(&config, &dir, &endpoint, status.as_u16(), started_at, &body).await,
+                                };
+                                match save_result {
+                                    Ok(saved) => {
+                                        record_save(&app_state, &saved, status.as_u16());
+                                        record_manifest(&app_state, &config, &saved, status.as_u16()).await;
+                                        save_backup(&app_state, &config, &saved).await;
+                                        if let Some(reason) = failure_reason {
+                                            if let Ok(mut app_guard) = app_state.lock() {
+                                                app_guard.record_failed_request(&endpoint, false);
+                                                app_guard.set_error(format!("API呼び出し失敗: {}", reason));
+                                            }
+                                            ApiCallOutcome::Rejected { status: status.as_u16(), reason, saved: Some(saved.path) }
+                                        } else {
+                                            clear_failed_request(&app_state, &endpoint);
+                                            save_headers(&app_state, &config, &saved, headers.as_ref()).await;
+                                            remember_cache_validators(&app_state, &endpoint, validators);
+                                            remember_body_hash(&app_state, &config, &endpoint, &body);
+                                            ApiCallOutcome::Success { status: status.as_u16(), saved: Some(saved) }
+                                        }
                                     }
+                                    Err(e) => ApiCallOutcome::SaveError { status: status.as_u16(), msg: e.to_string() },
                                 }
                             } else {
-                                log_message = "API呼び出し成功、JSON保存ディレクトリが見つかりません。".to_string();
+                                ApiCallOutcome::SaveError { status: status.as_u16(), msg: "JSON保存ディレクトリが見つかりません。".to_string() }
                             }
                         }
-                        Err(e) => {
-                            log_message = format!("API呼び出し成功、レスポンステキスト読み込み失敗: {}", e);
+                        Ok(ResponseBody::TempFile(temp)) => {
+                            // 一時ファイル経由の保存では本文の判定 (エラーエンベロープ・success_when) は行わない (本文をメモリに展開しないため)
+                            // require_json の検証だけは、本文をメモリに保持せずに行う
+                            if config.require_json && !DataManager::is_valid_json_file(&temp.path).await {
+                                DataManager::discard_temp_file(temp).await;
+                                record_failed_request(&app_state, &endpoint);
+                                ApiCallOutcome::Rejected { status: status.as_u16(), reason: "レスポンスがJSONではありません".to_string(), saved: None }
+                            } else {
+                                let meta = config.embed_meta.then(|| build_meta(&endpoint, status.as_u16(), started_at));
+                                match DataManager::save_streamed_response(temp, SaveOptions::from_config(&config, status.as_u16()), meta.as_ref()).await {
+                                    Ok(saved) => {
+                                        clear_failed_request(&app_state, &endpoint);
+                                        record_save(&app_state, &saved, status.as_u16());
+                                        record_manifest(&app_state, &config, &saved, status.as_u16()).await;
+                                        save_backup(&app_state, &config, &saved).await;
+                                        save_headers(&app_state, &config, &saved, headers.as_ref()).await;
+                                        remember_cache_validators(&app_state, &endpoint, validators);
+                                        ApiCallOutcome::Success { status: status.as_u16(), saved: Some(saved) }
+                                    }
+                                    Err(e) => ApiCallOutcome::SaveError { status: status.as_u16(), msg: e.to_string() },
+                                }
+                            }
+                        }
+                        Ok(ResponseBody::SchemaMismatch(reason)) => {
+                            record_failed_request(&app_state, &endpoint);
+                            ApiCallOutcome::Rejected { status: status.as_u16(), reason, saved: None }
+                        }
+                        Ok(ResponseBody::NoMatch) => {
+                            clear_failed_request(&app_state, &endpoint);
+                            ApiCallOutcome::NoMatch { status: status.as_u16() }
                         }
+                        Err(e) => ApiCallOutcome::ReadError { status: status.as_u16(), msg: body_error_message(&e) },
                     }
+                } else if !config.follow_redirects && status.is_redirection() {
+                    // リダイレクトを追わない設定では、3xx はエラーではなくリダイレクト先と合わせて記録する
+                    clear_failed_request(&app_state, &endpoint);
+                    let location = response.headers().get(LOCATION)
+                        .map(|location| String::from_utf8_lossy(location.as_bytes()).into_owned());
+                    ApiCallOutcome::Redirected { status: status.as_u16(), location }
                 } else {
-                    log_message = format!("API呼び出しエラー: ステータス {}", response.status());
+                    record_failed_request(&app_state, &endpoint);
+                    ApiCallOutcome::HttpError { status: status.as_u16() }
                 }
             }
             Err(e) => {
-                log_message = format!("API呼び出し失敗: {}", e);
+                record_failed_request(&app_state, &endpoint);
+                ApiCallOutcome::NetworkError { msg: request_error_message(&e) }
             }
         }
+    };
+
+    // 初回もレスポンスを保存した場合も、ステータスのみの場合と同じく初回フラグを下ろして結果を表示する
+    if is_first_call && config.save_first_response
+        && let Ok(mut app_guard) = app_state.lock()
+    {
+        app_guard.is_first_api_call = false;
+        if outcome.is_success()
+            && let Some(status) = outcome.status().and_then(|status| StatusCode::from_u16(status).ok())
+        {
+            app_guard.set_status_message(format!("初回API呼び出し成功: ステータス {}", status));
+        }
+    }
+
+    (outcome, CallMetrics { elapsed: started_at.elapsed(), body_bytes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppMode;
+    use crate::config::{BasicAuth, IpVersion, StatusPattern, DEFAULT_USER_AGENT};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_app(config: &Config) -> Arc<Mutex<App>> {
+        Arc::new(Mutex::new(App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config.clone()))))
+    }
+
+    // モックサーバーに /items を用意し、そのURLを返す
+    async fn mock_items(server: &MockServer, status: u16, body: &str) -> String {
+        Mock::given(method("GET")).and(path("/items"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .mount(server)
+            .await;
+        format!("{}/items", server.uri())
+    }
+
+    #[tokio::test]
+    async fn first_call_records_status_only() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 200, r#"{"id":1}"#).await;
+        let config = Arc::new(Config::default());
+        let app_state = test_app(&config);
+        let dir = std::env::temp_dir().join(format!("budilnik-http-first-{}", std::process::id()));
+
+        let (outcome, metrics) = fetch_api_data(Client::new(), true, endpoint, Some(dir.clone()), Arc::clone(&app_state), config).await;
+
+        assert!(matches!(outcome, ApiCallOutcome::Success { status: 200, saved: None }));
+        assert_eq!(metrics.body_bytes, None);
+        assert!(!dir.exists());
+        let app = app_state.lock().unwrap();
+        assert!(!app.is_first_api_call);
+        assert_eq!(app.status_message.as_deref(), Some("初回API呼び出し成功: ステータス 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn later_call_saves_response_file() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 200, r#"{"id":1}"#).await;
+        let config = Arc::new(Config::default());
+        let app_state = test_app(&config);
+        let dir = std::env::temp_dir().join(format!("budilnik-http-save-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let (outcome, _) = fetch_api_data(Client::new(), false, endpoint, Some(dir.clone()), app_state, config).await;
+
+        let saved = match outcome {
+            ApiCallOutcome::Success { status: 200, saved: Some(saved) } => saved,
+            other => panic!("unexpected outcome: {:?}", other),
+        };
+        let content = tokio::fs::read(&saved.path).await;
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        assert!(saved.path.starts_with(&dir));
+        let saved_json: Value = serde_json::from_slice(&content.unwrap()).unwrap();
+        assert_eq!(saved_json["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn basic_auth_is_sent_with_custom_headers() {
+        let server = MockServer::start().await;
+        // "user:" (空のパスワード) を Base64 にしたもの
+        Mock::given(method("GET")).and(path("/items"))
+            .and(header("authorization", "Basic dXNlcjo=")).and(header("x-api-key", "key"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let config = Config {
+            basic_auth: Some(BasicAuth { username: "user".to_string(), password: String::new() }),
+            headers: [("X-Api-Key".to_string(), "key".to_string())].into(),
+            ..Config::default()
+        };
+        let client = build_client(&config).unwrap();
+        let config = Arc::new(config);
+
+        let (outcome, _) = fetch_api_data(client, true, format!("{}/items", server.uri()), None, test_app(&config), config).await;
+
+        // 資格情報が一致しなければモックサーバーは 404 を返す
+        assert!(matches!(outcome, ApiCallOutcome::Success { status: 200, saved: None }));
+        let debug = format!("{:?}", BasicAuth { username: "user".to_string(), password: "secret".to_string() });
+        assert!(!debug.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn user_agent_is_sent_with_default_and_configured_value() {
+        let server = MockServer::start().await;
+        for user_agent in [DEFAULT_USER_AGENT, "my-poller/2.0"] {
+            Mock::given(method("GET")).and(path("/items")).and(header("user-agent", user_agent))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&server)
+                .await;
+        }
+        let mut outcomes = Vec::new();
+        for config in [Config::default(), Config { user_agent: "my-poller/2.0".to_string(), ..Config::default() }] {
+            let client = build_client(&config).unwrap();
+            let config = Arc::new(config);
+            outcomes.push(fetch_api_data(client, true, format!("{}/items", server.uri()), None, test_app(&config), config).await.0);
+        }
+
+        // User-Agent が一致しなければモックサーバーは 404 を返す
+        assert!(outcomes.iter().all(|outcome| matches!(outcome, ApiCallOutcome::Success { status: 200, .. })));
+        assert!(DEFAULT_USER_AGENT.starts_with("budilnik/"));
+    }
+
+    #[tokio::test]
+    async fn ip_version_limits_addresses_to_connect() {
+        // モックサーバーは 127.0.0.1 で待ち受けるため、IPv6 に固定すると接続先がなくなる
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 200, "{}").await;
+        let mut outcomes = Vec::new();
+        for ip_version in [IpVersion::V4, IpVersion::V6] {
+            let config = Config { ip_version, max_retries: 0, ..Config::default() };
+            let client = build_client(&config).unwrap();
+            let config = Arc::new(config);
+            outcomes.push(fetch_api_data(client, true, endpoint.clone(), None, test_app(&config), config).await.0);
+        }
+
+        assert!(matches!(outcomes[0], ApiCallOutcome::Success { status: 200, saved: None }));
+        assert!(matches!(outcomes[1], ApiCallOutcome::NetworkError { .. }));
+    }
+
+    #[tokio::test]
+    async fn response_over_max_bytes_is_not_saved() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 200, &format!(r#"{{"data":"{}"}}"#, "a".repeat(64))).await;
+        let config = Arc::new(Config { max_response_bytes: Some(32), ..Config::default() });
+        let dir = std::env::temp_dir().join(format!("budilnik-http-max-bytes-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let (outcome, _) = fetch_api_data(Client::new(), false, endpoint, Some(dir.clone()), test_app(&config), config).await;
+
+        let entries = std::fs::read_dir(&dir).unwrap().count();
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        match outcome {
+            ApiCallOutcome::ReadError { status: 200, msg } => assert!(msg.contains("max_response_bytes (32 バイト)")),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert_eq!(entries, 0);
+    }
+
+    #[tokio::test]
+    async fn extract_saves_only_matched_value() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 200, r#"{"meta":{"page":1},"data":{"items":[1,2,3]}}"#).await;
+        let config = Arc::new(Config { extract: Some("$.data.items".to_string()), ..Config::default() });
+        let dir = std::env::temp_dir().join(format!("budilnik-http-extract-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let (saved_outcome, _) = fetch_api_data(Client::new(), false, endpoint, Some(dir.clone()), test_app(&config), Arc::clone(&config)).await;
+        let no_match_config = Arc::new(Config { extract: Some("$.missing".to_string()), ..Config::default() });
+        let (no_match_outcome, _) = fetch_api_data(Client::new(), false, format!("{}/items", server.uri()), Some(dir.clone()), test_app(&no_match_config), no_match_config).await;
+
+        let saved = match saved_outcome {
+            ApiCallOutcome::Success { saved: Some(saved), .. } => saved,
+            other => panic!("unexpected outcome: {:?}", other),
+        };
+        let content = tokio::fs::read(&saved.path).await.unwrap();
+        // manifest.json を除いて、抽出した値を保存したファイルだけが残る
+        let entries = std::fs::read_dir(&dir).unwrap().filter(|entry| entry.as_ref().unwrap().file_name() != MANIFEST_FILENAME).count();
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        assert_eq!(content, b"[1,2,3]");
+        assert!(matches!(no_match_outcome, ApiCallOutcome::NoMatch { status: 200 }));
+        assert_eq!(entries, 1);
+    }
+
+    #[tokio::test]
+    async fn body_not_matching_schema_is_not_saved() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 200, r#"{"id":"abc"}"#).await;
+        let schema = serde_json::json!({"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}});
+        let config = Arc::new(Config { schema: Some(Arc::new(jsonschema::validator_for(&schema).unwrap())), ..Config::default() });
+        let dir = std::env::temp_dir().join(format!("budilnik-http-schema-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let (outcome, _) = fetch_api_data(Client::new(), false, endpoint, Some(dir.clone()), test_app(&config), Arc::clone(&config)).await;
+
+        let entries = std::fs::read_dir(&dir).unwrap().count();
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        match outcome {
+            ApiCallOutcome::Rejected { status: 200, reason, saved: None } => assert!(reason.contains("/id"), "{}", reason),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert_eq!(entries, 0);
+        assert!(schema_violation(&config, br#"{"id":1}"#).is_none());
+    }
+
+    #[tokio::test]
+    async fn expected_statuses_decide_success_and_204_is_not_saved() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 204, "").await;
+        let config = Arc::new(Config { require_json: true, ..Config::default() });
+        let dir = std::env::temp_dir().join(format!("budilnik-http-no-content-{}", std::process::id()));
+
+        let (outcome, _) = fetch_api_data(Client::new(), false, endpoint.clone(), Some(dir.clone()), test_app(&config), config).await;
+        assert!(matches!(outcome, ApiCallOutcome::NoContent));
+        assert!(outcome.is_success());
+        assert!(!dir.exists());
+
+        let expected: Vec<StatusPattern> = serde_json::from_str(r#"[200, 201]"#).unwrap();
+        let config = Arc::new(Config { expected_statuses: expected, ..Config::default() });
+        let (outcome, _) = fetch_api_data(Client::new(), false, endpoint, Some(dir.clone()), test_app(&config), config).await;
+        assert!(matches!(outcome, ApiCallOutcome::HttpError { status: 204 }));
+    }
+
+    #[tokio::test]
+    async fn server_error_produces_error_outcome() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 500, "internal error").await;
+        let config = Arc::new(Config { max_retries: 0, ..Config::default() });
+        let app_state = test_app(&config);
+        let dir = std::env::temp_dir().join(format!("budilnik-http-error-{}", std::process::id()));
+
+        let (outcome, _) = fetch_api_data(Client::new(), false, endpoint.clone(), Some(dir.clone()), Arc::clone(&app_state), config).await;
+
+        assert!(matches!(outcome, ApiCallOutcome::HttpError { status: 500 }));
+        assert!(!outcome.is_success());
+        assert!(!dir.exists());
+        assert_eq!(app_state.lock().unwrap().last_failed_request.as_ref().map(|failed| failed.endpoint.as_str()), Some(endpoint.as_str()));
+    }
+
+    #[tokio::test]
+    async fn first_call_server_error_is_not_treated_as_success() {
+        let server = MockServer::start().await;
+        let endpoint = mock_items(&server, 500, "internal error").await;
+        let config = Arc::new(Config { max_retries: 0, ..Config::default() });
+        let app_state = test_app(&config);
+
+        let (outcome, _) = fetch_api_data(Client::new(), true, endpoint.clone(), None, Arc::clone(&app_state), config).await;
+
+        assert!(matches!(outcome, ApiCallOutcome::HttpError { status: 500 }));
+        let app = app_state.lock().unwrap();
+        // 失敗しても以降はスケジュールどおりに呼び出し、'R' キーでも再試行できる
+        assert!(!app.is_first_api_call);
+        let failed = app.last_failed_request.as_ref().unwrap();
+        assert_eq!(failed.endpoint, endpoint);
+        assert!(failed.is_first_call);
+    }
+
+    #[test]
+    fn query_is_encoded_and_appended_to_existing_query() {
+        let mut config = Config::default();
+        config.query.insert("limit".to_string(), "100".to_string());
+        config.query.insert("q".to_string(), "a b&c".to_string());
+        let client = Client::new();
+
+        let request = build_request(&client, &config, "https://api.example.com/items?format=json", None).build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.example.com/items?format=json&limit=100&q=a+b%26c");
+
+        let request = build_request(&client, &Config::default(), "https://api.example.com/items", None).build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.example.com/items");
+    }
+
+    #[test]
+    fn invalid_configured_proxy_fails_client_build() {
+        let config = Config { http_proxy: Some("http://proxy example:8080".to_string()), ..Config::default() };
+        let err = build_client(&config).unwrap_err().to_string();
+        assert!(err.contains("'http_proxy' (設定ファイル"));
+
+        let config = Config { https_proxy: Some("http://proxy.example.com:8080".to_string()), ..Config::default() };
+        assert!(build_client(&config).is_ok());
+    }
+
+    #[test]
+    fn unchanged_body_is_detected_per_endpoint() {
+        let config = Config { skip_unchanged: true, ..Config::default() };
+        let app_state = Arc::new(Mutex::new(App::new(crate::app::AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config.clone()))));
+        assert!(!is_unchanged_body(&app_state, "https://a.example", b"{}"));
+
+        remember_body_hash(&app_state, &config, "https://a.example", b"{}");
+        assert!(is_unchanged_body(&app_state, "https://a.example", b"{}"));
+        assert!(!is_unchanged_body(&app_state, "https://a.example", b"{\"a\":1}"));
+        assert!(!is_unchanged_body(&app_state, "https://b.example", b"{}"));
     }
-    log_message
-}
\ No newline at end of file
+}
@@ -1,5 +1,6 @@
 use crossterm::{
-    event::{self, Event as CrosstermEvent, KeyCode},
+    cursor,
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event as CrosstermEvent, MouseEventKind},
     execute,
     terminal::{
         disable_raw_mode, enable_raw_mode, EnterAlternateScreen,
@@ -11,23 +12,40 @@ use ratatui::{
     Terminal,
 };
 use std::{
-    io::{self, stdout},
+    io::{self, stdout, IsTerminal},
+    process::ExitCode,
     sync::{Arc, Mutex},
     time::Duration,
 };
 use std::path::PathBuf;
-use tokio::time::sleep;
+use notify_rust::Notification;
+use tokio::time::{interval, interval_at, sleep, MissedTickBehavior};
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 
 mod app;
 mod ui;
 mod config;
 mod data;
 mod http;
+mod metrics;
+mod rate_limit;
+mod state;
+mod webhook;
 
-use app::{App, AppMode};
+use app::{App, AppAction, AppMode, LogLevel, TriggerTarget};
 use config::Config;
-use data::DataManager;
+use data::{DataManager, SavedFile};
+use http::{ApiCallOutcome, CallMetrics};
+use state::{ClockState, SavedStats};
+
+const DEFAULT_CONFIG_PATH: &str = "config.json";
+
+// 画面を更新する間隔 (実行の判定の間隔 check_interval_ms とは別)
+const UI_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+// 終了時に実行中のAPI呼び出しの完了を待つ最大の時間
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
 
 // 各タスク間でやり取りするイベントの種類を定義
 #[derive(Debug)]
@@ -41,35 +59,382 @@ enum AppEvent {
         is_first_call: bool,
         json_dir: Option<PathBuf>,
     },
-    ApiCallCompleted(String), // API呼び出し完了メッセージ
+    // API呼び出しの結果 (ログの文言はメインループで組み立てる)
+    ApiCallCompleted {
+        endpoint: String,
+        is_first_call: bool,
+        outcome: ApiCallOutcome,
+        metrics: CallMetrics,
+    },
+    // SIGINT / SIGTERM などを受け取った (シグナル名)
+    ShutdownSignal(&'static str),
 }
 
-#[tokio::main]
-async fn main() -> io::Result<()> {
-    // ターミナルセットアップ
-    enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+// パニック時にもターミナルを復元してからパニック内容を表示するフックを登録する
+// (raw モードや代替スクリーンが残ったままになるのを防ぐ)
+fn install_panic_hook() {
+    install_panic_hook_with(|| {
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen, DisableMouseCapture, cursor::Show);
+    });
+}
+
+// restore を実行してから既定のフックでパニック内容を表示する (どのスレッドのパニックでも呼ばれる)
+fn install_panic_hook_with(restore: impl Fn() + Send + Sync + 'static) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        default_hook(info);
+    }));
+}
+
+// API呼び出しの失敗をデスクトップ通知で知らせる
+// 通知の表示は完了まで待つことがあるため別スレッドで行い、表示できなくてもログに残すだけにする
+fn notify_error(app: Arc<Mutex<App>>, message: String) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = Notification::new().summary("budilnik: API呼び出し失敗").body(&message).show() {
+            app.lock().unwrap().add_log_with_level(LogLevel::Warn, format!("デスクトップ通知を表示できませんでした: {}", e));
+        }
+    });
+}
 
+// 呼び出しの結果を webhook_url に POST する (UIを止めないよう別タスクで送り、失敗しても警告のログを出すだけにする)
+fn send_webhook(app: Arc<Mutex<App>>, client: reqwest::Client, url: String, endpoint: String, status: Option<u16>) {
+    let payload_time = chrono::Local::now();
+    tokio::spawn(async move {
+        let payload = webhook::WebhookPayload::new(status, payload_time, &endpoint);
+        if let Err(e) = webhook::send(&client, &url, &payload).await {
+            app.lock().unwrap().add_log_with_level(LogLevel::Warn, format!("Webhookの送信に失敗しました: {}", e));
+        }
+    });
+}
+
+// すべてのエンドポイントについて初回のAPI呼び出しをトリガーする
+async fn trigger_initial_calls(app: &Arc<Mutex<App>>, event_tx: &mpsc::Sender<AppEvent>) {
+    let targets = app.lock().unwrap().trigger_targets();
+    let is_first = app.lock().unwrap().is_first_api_call; // 初回フラグを取得
+
+    // エンドポイントごとに AppEvent::ApiCallTriggered イベントを送信し、is_first_call を含める
+    let mut send_failed = false;
+    for (endpoint, json_dir) in targets {
+        if event_tx.send(AppEvent::ApiCallTriggered {
+            endpoint,
+            is_first_call: is_first,
+            json_dir,
+        }).await.is_err() {
+            send_failed = true;
+            break;
+        }
+    }
+    if send_failed {
+        eprintln!("Failed to send initial API call trigger.");
+        if let Ok(mut app_guard) = app.lock() {
+            app_guard.set_error("初期API呼び出しトリガーの送信に失敗しました。".to_string());
+        }
+    } else if let Ok(mut app_guard) = app.lock() {
+        app_guard.set_status_message("アプリケーション起動: 初回API呼び出しをトリガーしました。".to_string());
+    }
+}
+
+// 実行の判定の間隔を作る (check_interval_ms。0 の場合は UI_TICK_INTERVAL)
+fn check_timer(check_interval_ms: u64) -> tokio::time::Interval {
+    let period = if check_interval_ms == 0 { UI_TICK_INTERVAL } else { Duration::from_millis(check_interval_ms) };
+    let mut timer = interval_at(tokio::time::Instant::now() + period, period);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+// 日付の変わり目と実行時刻 (定刻モード) / タイマー (クロックモード) を確認し、必要ならAPI呼び出しをトリガーする
+// クロックモードのタイマーは、前回の判定から実際に経過した時間だけ進める
+async fn check_api_trigger(
+    app: &Arc<Mutex<App>>,
+    event_tx: &mpsc::Sender<AppEvent>,
+    last_check: &mut std::time::Instant,
+    last_debug_log: &mut Option<std::time::Instant>,
+) {
+    let elapsed = chrono::Duration::from_std(last_check.elapsed()).unwrap_or(chrono::Duration::zero());
+    *last_check = std::time::Instant::now();
+
+    // 日付が変わっていたら、実行の判定より先に新しい日付のディレクトリを用意する (0時ちょうどの実行も新しい日付に保存する)
+    let today = chrono::Local::now().date_naive();
+    let rollover_base_dir = app.lock().unwrap().begin_date_rollover(today);
+    if let Some(base_dir) = rollover_base_dir {
+        let endpoints = app.lock().unwrap().api_endpoints.clone();
+        let result = setup_data_dirs(DataManager::setup_date_dir(&base_dir, today).await, &endpoints).await;
+        let mut app_guard = app.lock().unwrap();
+        match result {
+            Ok((path, endpoint_dirs)) => app_guard.finish_date_rollover(path, endpoint_dirs),
+            Err(e) => app_guard.set_error(format!("日付が変わりましたが、新しいデータディレクトリを作成できませんでした (前日のディレクトリに保存します): {}", e)),
+        }
+    }
+
+    let api_trigger_params = {
+        let mut app_guard = app.lock().unwrap();
+
+        let mut params: Option<(Vec<TriggerTarget>, bool)> = None;
+
+        // デバッグ用: この判定 (実行 / 待機 / スキップ) とその理由
+        let now = chrono::Local::now().naive_local();
+        app_guard.check_clock_adjustment(now, std::time::Instant::now());
+        let (decision, reason) = if app_guard.api_endpoints.is_empty() || app_guard.today_json_dir.is_none() {
+            ("スキップ", "設定またはデータディレクトリが未準備".to_string())
+        } else if app_guard.paused {
+            // 一時停止中はタイマーを進めず、定刻のチェックも行わない (時刻の表示だけ更新する)
+            ("スキップ", "一時停止中".to_string())
+        } else if app_guard.is_first_api_call {
+            // is_first_api_callがtrueの場合はタイマーによるAPI呼び出しは行わない
+            // 初回API呼び出しは起動時にAppEvent::ApiCallTriggeredで処理されるため
+            ("スキップ", "初回API呼び出しの完了待ち".to_string())
+        } else if app_guard.mode == AppMode::OnTimeMode {
+            let next_trigger_time = app_guard.next_trigger_time;
+            match next_trigger_time {
+                None => ("スキップ", "次回実行時刻が未設定".to_string()),
+                Some(next_trigger) if now < next_trigger => ("待機", "実行時刻前".to_string()),
+                // レート制限の待機中は、待機が明けるまで実行を遅らせる
+                Some(_) if app_guard.is_rate_limited(now) => ("待機", "レート制限による延期中".to_string()),
+                Some(next_trigger) => {
+                    app_guard.set_status_message(format!("定刻モード: {}にAPI実行をトリガーします。", next_trigger.format("%H:%M:%S")));
+                    params = Some((
+                        app_guard.trigger_targets(),
+                        false, // タイマーからの呼び出しは常に初回ではない
+                    ));
+                    app_guard.last_fired_trigger = Some(next_trigger);
+                    app_guard.set_next_trigger_time();
+                    ("実行", "実行時刻に到達".to_string())
+                }
+            }
+        } else {
+            app_guard.decrement_timer(elapsed);
+            if app_guard.remaining_duration > chrono::Duration::zero() {
+                ("待機", "タイマーが0になっていない".to_string())
+            } else if app_guard.is_rate_limited(now) {
+                // レート制限の待機中は、タイマーが0のまま待機が明けるのを待つ
+                ("待機", "レート制限による延期中".to_string())
+            } else {
+                app_guard.set_status_message("クロックモード: タイマーが0になりました。API実行をトリガーします。".to_string());
+                params = Some((
+                    app_guard.trigger_targets(),
+                    false, // タイマーからの呼び出しは常に初回ではない
+                ));
+                app_guard.reset_timer();
+                ("実行", "タイマーが0になった".to_string())
+            }
+        };
+
+        let next_deadline = match app_guard.mode {
+            AppMode::OnTimeMode => app_guard.next_trigger_time,
+            AppMode::ClockMode => now.checked_add_signed(app_guard.remaining_duration),
+        };
+        let mode = app_guard.mode;
+        // check_interval_ms が短い場合にログが埋まらないよう、待機中の判定は UI_TICK_INTERVAL に1回だけ残す
+        if decision == "実行" || last_debug_log.is_none_or(|logged_at| logged_at.elapsed() >= UI_TICK_INTERVAL) {
+            *last_debug_log = Some(std::time::Instant::now());
+            app_guard.add_debug_log(format!(
+                "tick: モード={:?} 現在={} 次回={} 判定={} (理由: {})",
+                mode,
+                now.format("%H:%M:%S"),
+                next_deadline.map_or("-".to_string(), |deadline| deadline.format("%Y-%m-%d %H:%M:%S").to_string()),
+                decision,
+                reason
+            ));
+        }
+        params
+    };
+
+    // ここで直接 http::fetch_api_data を呼び出す代わりに、エンドポイントごとにイベントを送信する
+    if let Some((targets, is_first_call)) = api_trigger_params {
+        for (endpoint, json_dir) in targets {
+            if event_tx.send(AppEvent::ApiCallTriggered {
+                endpoint,
+                is_first_call,
+                json_dir,
+            }).await.is_err() {
+                eprintln!("Failed to send API call trigger from timer task.");
+                return;
+            }
+        }
+    }
+}
+
+// 日付ディレクトリの作成結果に、エンドポイントごとの保存先を加える
+// 複数のエンドポイントを呼び出す場合は、ファイル名が衝突しないようエンドポイントごとのディレクトリに保存する
+async fn setup_data_dirs(today_dir: anyhow::Result<PathBuf>, endpoints: &[String]) -> anyhow::Result<(PathBuf, Vec<PathBuf>)> {
+    match today_dir {
+        Ok(path) if endpoints.len() > 1 => DataManager::setup_endpoint_dirs(&path, endpoints).await
+            .map(|endpoint_dirs| (path, endpoint_dirs)),
+        Ok(path) => Ok((path, Vec::new())),
+        Err(e) => Err(e),
+    }
+}
+
+// コマンドライン引数から設定ファイルのパスを決める
+// `budilnik <path>` または `budilnik --config <path>`。指定がなければ config.json
+fn config_path_from_args(mut args: impl Iterator<Item = String>) -> String {
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            if let Some(path) = args.next() {
+                return path;
+            }
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            return path.to_string();
+        } else if !arg.starts_with("--") {
+            return arg;
+        }
+    }
+    DEFAULT_CONFIG_PATH.to_string()
+}
+
+// SIGINT / SIGTERM (Unix では端末を閉じたときの SIGHUP も) を待ち、受け取ったシグナルの名前を返す
+// raw モードでは Ctrl+C はキー入力として届くため、SIGINT は kill などで送られた場合のみ受け取る
+async fn wait_for_shutdown_signal() -> io::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.map(|()| "SIGINT"),
+            _ = terminate.recv() => Ok("SIGTERM"),
+            _ = hangup.recv() => Ok("SIGHUP"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.map(|()| "Ctrl+C")
+    }
+}
+
+// コマンドライン引数に指定したフラグ (`--no-alt-screen` など) が含まれているかどうか
+fn has_flag(mut args: impl Iterator<Item = String>, flag: &str) -> bool {
+    args.any(|arg| arg == flag)
+}
+
+// ステータスコードを "404 Not Found" のような表示用の文字列にする
+fn status_text(status: u16) -> String {
+    reqwest::StatusCode::from_u16(status).map_or_else(|_| status.to_string(), |status| status.to_string())
+}
+
+// API呼び出しの結果をログに表示するメッセージにする
+fn outcome_log_message(is_first_call: bool, outcome: &ApiCallOutcome, config: &Config) -> String {
+    match outcome {
+        ApiCallOutcome::Success { status, saved: None } if is_first_call => format!("初回API呼び出し完了 (ステータス: {})", status_text(*status)),
+        ApiCallOutcome::Success { status, saved: None } => format!("API呼び出し成功 (ステータス: {})", status_text(*status)),
+        ApiCallOutcome::Success { saved: Some(saved), .. } => saved_log_message(config, saved),
+        ApiCallOutcome::Rejected { reason, saved: Some(_), .. } => format!("API呼び出し失敗: {}, errors/ に保存しました", reason),
+        ApiCallOutcome::Rejected { reason, saved: None, .. } => format!("API呼び出し失敗: {} (保存をスキップしました)", reason),
+        ApiCallOutcome::HttpError { status } => format!("API呼び出しエラー: ステータス {}", status_text(*status)),
+        ApiCallOutcome::NetworkError { msg } if is_first_call => format!("初回API呼び出し失敗: {}", msg),
+        ApiCallOutcome::NetworkError { msg } => format!("API呼び出し失敗: {}", msg),
+        ApiCallOutcome::ReadError { msg, .. } => format!("API呼び出し成功、レスポンステキスト読み込み失敗: {}", msg),
+        ApiCallOutcome::SaveError { msg, .. } => format!("API呼び出し成功、JSON保存失敗: {}", msg),
+        ApiCallOutcome::NotModified => "変更なし (304)".to_string(),
+        ApiCallOutcome::NoContent => "API呼び出し成功: 本文なし (204) のため保存をスキップしました".to_string(),
+        ApiCallOutcome::Unchanged { .. } => "内容に変更なし (保存をスキップしました)".to_string(),
+        ApiCallOutcome::NoMatch { .. } => format!(
+            "extract ({}) に一致する値がないため、保存をスキップしました",
+            config.extract.as_deref().unwrap_or_default()
+        ),
+        ApiCallOutcome::Redirected { status, location } => format!(
+            "リダイレクト (ステータス: {}) Location: {}",
+            status_text(*status),
+            location.as_deref().unwrap_or("(なし)")
+        ),
+        ApiCallOutcome::Skipped { reason } => reason.clone(),
+    }
+}
+
+// ログの末尾に付ける本文のサイズと所要時間 (本文を受信しなかった場合は所要時間のみ)
+fn metrics_log_suffix(metrics: &CallMetrics) -> String {
+    match metrics.body_bytes {
+        Some(bytes) => format!(" ({} bytes, {}ms)", bytes, metrics.elapsed.as_millis()),
+        None => format!(" ({}ms)", metrics.elapsed.as_millis()),
+    }
+}
+
+// 保存成功時のログメッセージ
+fn saved_log_message(config: &Config, saved: &SavedFile) -> String {
+    if !config.response_type.is_json() {
+        format!("API呼び出し成功: レスポンスを保存しました ({})", saved.path.file_name().unwrap_or_default().to_string_lossy())
+    } else if saved.sidecar.is_some() {
+        format!("API呼び出し成功: 警告: レスポンスがJSONではないため、メタデータをサイドカーファイルに保存しました ({})", chrono::Local::now().format("%H-%M-%S"))
+    } else if saved.pretty_fallback {
+        format!("API呼び出し成功: 警告: レスポンスがJSONではないため、整形せずにそのまま保存しました ({})", chrono::Local::now().format("%H-%M-%S"))
+    } else {
+        // JSONファイル名形式の変更に合わせてここも修正
+        format!("API呼び出し成功: JSONを保存しました ({})", chrono::Local::now().format("%H-%M-%S"))
+    }
+}
+
+
+// --headless で stdout に出力する1行 (時刻、エンドポイント、結果、保存したファイルをタブ区切りで並べる)
+fn headless_output_line(timestamp: &str, endpoint: &str, outcome: &ApiCallOutcome) -> String {
+    let saved_path = match outcome {
+        ApiCallOutcome::Success { saved: Some(saved), .. } => saved.path.display().to_string(),
+        ApiCallOutcome::Rejected { saved: Some(path), .. } => path.display().to_string(),
+        _ => "-".to_string(),
+    };
+    format!("{}\t{}\t{}\t{}", timestamp, endpoint, outcome_summary(outcome), saved_path)
+}
+
+// 結果の履歴パネルに表示する短い結果 (ステータスコードまたはエラー)
+fn outcome_summary(outcome: &ApiCallOutcome) -> String {
+    match outcome {
+        ApiCallOutcome::Success { status, .. } => status.to_string(),
+        ApiCallOutcome::Rejected { status, .. }
+        | ApiCallOutcome::HttpError { status }
+        | ApiCallOutcome::ReadError { status, .. }
+        | ApiCallOutcome::SaveError { status, .. } => format!("{} (失敗)", status),
+        ApiCallOutcome::NetworkError { msg } => format!("エラー: {}", msg),
+        ApiCallOutcome::NotModified => "304 (変更なし)".to_string(),
+        ApiCallOutcome::NoContent => "204 (本文なし)".to_string(),
+        ApiCallOutcome::Unchanged { status } => format!("{} (変更なし)", status),
+        ApiCallOutcome::NoMatch { status } => format!("{} (抽出なし)", status),
+        ApiCallOutcome::Redirected { status, .. } => format!("{} (リダイレクト)", status),
+        ApiCallOutcome::Skipped { .. } => "スキップ".to_string(),
+    }
+}
+
+#[tokio::main]
+async fn main() -> io::Result<ExitCode> {
     // --- Configの読み込み ---
-    let config_path = "config.json";
+    // 代替スクリーンを使うかどうかを決めるため、ターミナルのセットアップより前に読み込む
+    let config_path = config_path_from_args(std::env::args().skip(1));
     let app: Arc<Mutex<App>>; // AppのArc<Mutex>を宣言
 
-    let config_load_result = Config::load_from_file(config_path);
+    let config_load_result = Config::load_from_file(&config_path);
+    let no_alt_screen = has_flag(std::env::args().skip(1), "--no-alt-screen")
+        || config_load_result.as_ref().is_ok_and(|cfg| cfg.no_alt_screen);
+    // --headless の場合は画面を使わず、結果を stdout に、ログを stderr に出力する (パイプラインで使う場合)
+    let headless = has_flag(std::env::args().skip(1), "--headless");
+
+    // ターミナルセットアップ
+    // no_alt_screen の場合は通常の画面にそのまま描画し、終了後もスクロールバックに出力を残す
+    let mut terminal = if headless {
+        None
+    } else {
+        install_panic_hook();
+        enable_raw_mode()?;
+        let mut stdout = stdout();
+        if !no_alt_screen {
+            execute!(stdout, EnterAlternateScreen)?;
+        }
+        execute!(stdout, EnableMouseCapture)?; // マウスホイールでログをスクロールする
+        Some(Terminal::new(CrosstermBackend::new(stdout))?)
+    };
 
     // Configの読み込み結果に応じてAppを初期化
     match config_load_result {
         Ok(cfg) => {
-            eprintln!("Config loaded successfully: {:?}", cfg);
+            eprintln!("Config loaded successfully: {}", cfg.summary());
             let initial_mode = if cfg.on_time { AppMode::OnTimeMode } else { AppMode::ClockMode };
+            let time = cfg.primary_time();
             app = Arc::new(Mutex::new(App::new(
                 initial_mode,
-                cfg.time.h,
-                cfg.time.m,
-                cfg.time.s,
-                cfg.api.clone(),
+                time.h,
+                time.m,
+                time.s,
+                cfg.api.as_slice().to_vec(),
+                Arc::new(cfg),
             )));
         },
         Err(e) => {
@@ -77,42 +442,145 @@ async fn main() -> io::Result<()> {
             app = Arc::new(Mutex::new(App::new(
                 AppMode::ClockMode, // デフォルトモード (エラー表示のみで機能しない)
                 0, 0, 0, // 時間も0に
-                "".to_string(), // APIエンドポイントも空に
+                Vec::new(), // APIエンドポイントも空に
+                Arc::new(Config::default()),
             )));
-            app.lock().unwrap().set_error(format!("設定ファイルの読み込みに失敗しました: {}. 機能を停止します。", e));
+            app.lock().unwrap().set_error(format!("設定ファイル '{}' の読み込みに失敗しました: {}. 機能を停止します (終了コード: 2)。", config_path, e));
         }
     };
+    app.lock().unwrap().log_to_stderr = headless;
 
 
     // --- 初回起動時のディレクトリセットアップ ---
-    let mut app_guard = app.lock().unwrap(); // ロックを一回取得
     let mut should_trigger_initial_api_call = false; // 初回API呼び出しをトリガーするかどうかのフラグ
+    // HTTPクライアントは起動時に1度だけ作成し、すべてのAPI呼び出しで共有する (接続プールやTLSセッションを使い回す)
+    let config = Arc::clone(&app.lock().unwrap().config);
+    let mut http_client = match http::build_client(&config) {
+        Ok(client) => {
+            if let Some(message) = config.ip_version.forced_message() {
+                app.lock().unwrap().add_log(message.to_string());
+            }
+            Some(client)
+        }
+        Err(e) => {
+            app.lock().unwrap().set_error(format!("HTTPクライアントの作成に失敗しました (終了コード: 2): {}", e));
+            None
+        }
+    };
+    // Webhook 用のクライアントは設定の再読み込みで webhook_url が追加された場合にも使えるよう、常に作成しておく
+    let webhook_client = match webhook::build_client() {
+        Ok(client) => Some(client),
+        Err(e) => {
+            app.lock().unwrap().add_log_with_level(LogLevel::Warn, format!("Webhook用のHTTPクライアントの作成に失敗しました: {}", e));
+            None
+        }
+    };
+    let config_loaded = !app.lock().unwrap().api_endpoints.is_empty() && http_client.is_some();
+
+    if config_loaded { // Configが正常に読み込まれた場合のみ実行
+        // ログファイルは起動時に1度だけ開き、以降のログを追記する
+        let log_file = app.lock().unwrap().config.log_file.clone();
+        if let Some(log_file) = log_file {
+            let mut app_guard = app.lock().unwrap();
+            match app_guard.open_log_file(&log_file) {
+                Ok(()) => app_guard.set_status_message(format!("ログを '{}' に追記します。", log_file.display())),
+                Err(e) => app_guard.set_error(format!("ログファイル '{}' を開けませんでした: {}", log_file.display(), e)),
+            }
+        }
+        // 以前の実行までの呼び出しの累計を引き継ぐ (壊れたファイルは使わずに0から数える)
+        let stats_file = app.lock().unwrap().config.stats_file.clone();
+        if let Some(stats_file) = stats_file {
+            let load_result = SavedStats::load(&stats_file).await;
+            let mut app_guard = app.lock().unwrap();
+            match load_result {
+                Ok(Some(stats)) => {
+                    app_guard.restore_stats(&stats);
+                    app_guard.add_log(format!("'{}' から呼び出しの累計 ({}回) を読み込みました。", stats_file.display(), stats.calls_total));
+                }
+                Ok(None) => {}
+                Err(e) => app_guard.add_log_with_level(LogLevel::Warn, format!("統計ファイル '{}' を読み込めないため、累計を0から数えます: {}", stats_file.display(), e)),
+            }
+            app_guard.stats_path = Some(stats_file);
+        }
+        let run_name = app.lock().unwrap().config.run_name.clone();
+        let output_dir = app.lock().unwrap().config.output_dir.clone();
+        if let Some(run_name) = &run_name {
+            app.lock().unwrap().set_status_message(format!("実行名: {}", run_name));
+        }
+        // 証明書の検証を無効にしている場合は、ログで見落とさないよう目立つ警告を出す
+        if app.lock().unwrap().config.danger_accept_invalid_certs {
+            app.lock().unwrap().add_log_with_level(LogLevel::Warn, "!!! danger_accept_invalid_certs が有効です。サーバー証明書を検証せずに通信します (中間者攻撃を防げません) !!!".to_string());
+        }
+        if app.lock().unwrap().config.dry_run {
+            app.lock().unwrap().set_status_message("ドライラン: APIは呼び出さず、実行のタイミングだけをログに出力します。".to_string());
+        }
+        // 保存期間を過ぎた日付ディレクトリを削除する
+        let retention_days = app.lock().unwrap().config.retention_days;
+        if let Some(retention_days) = retention_days {
+            let base_dir = DataManager::base_dir(&output_dir, run_name.as_deref());
+            let cleanup_result = DataManager::cleanup_old_directories(&base_dir, retention_days).await;
+            let mut app_guard = app.lock().unwrap();
+            match cleanup_result {
+                Ok(report) => {
+                    for path in &report.removed {
+                        app_guard.set_status_message(format!("保存期間 ({}日) を過ぎたディレクトリを削除しました: {}", retention_days, path.display()));
+                    }
+                    for (path, reason) in &report.failed {
+                        app_guard.set_error(format!("古いディレクトリの削除に失敗しました: {} ({})", path.display(), reason));
+                    }
+                }
+                Err(e) => app_guard.set_error(format!("古いディレクトリの確認に失敗しました: {}", e)),
+            }
+        }
 
-    if !app_guard.api_endpoint.is_empty() { // Configが正常に読み込まれた場合のみ実行
-        drop(app_guard); // ロックを解放
-        let today_dir_result = DataManager::setup_directories().await;
-        app_guard = app.lock().unwrap(); // 再度ロック
-        match today_dir_result {
-            Ok(path) => {
-                app_guard.today_json_dir = Some(path.clone());
-                app_guard.set_status_message(format!("データディレクトリ '{}' をセットアップしました。", path.display()));
-                should_trigger_initial_api_call = true; // ディレクトリセットアップ成功時に初回API呼び出しを許可
-            },
-            Err(e) => {
-                app_guard.set_error(format!("データディレクトリのセットアップに失敗しました: {}", e));
+        let endpoints = app.lock().unwrap().api_endpoints.clone();
+        let today_dir_result = setup_data_dirs(DataManager::setup_directories(&output_dir, run_name.as_deref()).await, &endpoints).await;
+        { // ロックのスコープ
+            let mut app_guard = app.lock().unwrap();
+            match today_dir_result {
+                Ok((path, endpoint_dirs)) => {
+                    app_guard.today_json_dir = Some(path.clone());
+                    app_guard.endpoint_json_dirs = endpoint_dirs;
+                    app_guard.set_status_message(format!("データディレクトリ '{}' をセットアップしました。", path.display()));
+                    should_trigger_initial_api_call = true; // ディレクトリセットアップ成功時に初回API呼び出しを許可
+                },
+                Err(e) => {
+                    app_guard.set_error(format!("データディレクトリのセットアップに失敗しました (終了コード: 2): {}", e));
+                }
             }
+        } // ロックを解放
+
+        // クロックモードの場合、前回終了時の残り時間を復元する (設定された間隔が変わっていれば最初から数え直す)
+        if should_trigger_initial_api_call && app.lock().unwrap().mode == AppMode::ClockMode {
+            let state_path = ClockState::path(&DataManager::base_dir(&output_dir, run_name.as_deref()));
+            let load_result = ClockState::load(&state_path).await;
+            let mut app_guard = app.lock().unwrap();
+            match load_result {
+                Ok(Some(state)) => {
+                    if app_guard.restore_clock_state(&state) {
+                        app_guard.set_status_message(format!("前回の残り時間 ({}秒) を復元しました。", state.remaining_seconds));
+                    } else {
+                        app_guard.set_status_message("実行間隔が変更されたため、保存された残り時間は使用しません。".to_string());
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => app_guard.set_error(format!("保存された残り時間の読み込みに失敗しました: {}", e)),
+            }
+            app_guard.clock_state_path = Some(state_path);
         }
     } else {
         // Configエラーの場合はディレクトリセットアップも試みない
-        app_guard.set_error("設定ファイルに問題があるため、データディレクトリのセットアップはスキップされました。".to_string());
+        app.lock().unwrap().set_error("設定ファイルに問題があるため、データディレクトリのセットアップはスキップされました。".to_string());
     }
-    drop(app_guard); // ロックを解放
 
 
+    // 設定やディレクトリのセットアップに失敗した場合は、終了コード 2 で終了する
+    app.lock().unwrap().setup_failed = !should_trigger_initial_api_call;
+
     // 定刻モードの場合、次回のトリガー時刻を設定
     { // ロックのスコープ
         let mut app_guard = app.lock().unwrap();
-        if !app_guard.api_endpoint.is_empty() && app_guard.today_json_dir.is_some() && app_guard.mode == AppMode::OnTimeMode {
+        if !app_guard.api_endpoints.is_empty() && app_guard.today_json_dir.is_some() && app_guard.mode == AppMode::OnTimeMode {
             app_guard.set_next_trigger_time();
         }
     } // ロックを解放
@@ -125,101 +593,83 @@ async fn main() -> io::Result<()> {
     // --- 初回API呼び出しのトリガー ---
     // Config読み込みとディレクトリセットアップが成功した場合のみ
     if should_trigger_initial_api_call {
-        let current_endpoint = app.lock().unwrap().api_endpoint.clone();
-        let json_dir = app.lock().unwrap().today_json_dir.clone();
-        let is_first = app.lock().unwrap().is_first_api_call; // 初回フラグを取得
-
-        // AppEvent::ApiCallTriggered イベントを送信し、is_first_call を含める
-        if event_tx.send(AppEvent::ApiCallTriggered {
-            endpoint: current_endpoint,
-            is_first_call: is_first,
-            json_dir,
-        }).await.is_err() {
-            eprintln!("Failed to send initial API call trigger.");
-            if let Ok(mut app_guard) = app.lock() {
-                app_guard.set_error("初期API呼び出しトリガーの送信に失敗しました。".to_string());
-            }
+        let startup_delay_secs = app.lock().unwrap().config.startup_delay_secs;
+        if startup_delay_secs == 0 {
+            trigger_initial_calls(&app, &event_tx).await;
         } else {
-            if let Ok(mut app_guard) = app.lock() {
-                app_guard.set_status_message("アプリケーション起動: 初回API呼び出しをトリガーしました。".to_string());
-            }
+            // 再起動を繰り返してもAPIに負荷をかけないよう、初回の呼び出しを遅らせる (待っている間はステータス欄に残り秒数を表示する)
+            let app_clone_startup = Arc::clone(&app);
+            let event_tx_clone_startup = event_tx.clone();
+            tokio::spawn(async move {
+                for remaining in (1..=startup_delay_secs).rev() {
+                    app_clone_startup.lock().unwrap().startup_countdown = Some(remaining);
+                    sleep(Duration::from_secs(1)).await;
+                }
+                app_clone_startup.lock().unwrap().startup_countdown = None;
+                trigger_initial_calls(&app_clone_startup, &event_tx_clone_startup).await;
+            });
         }
     }
 
 
     // --- 各非同期タスクの起動 ---
 
-    // 1. Crosstermイベントリスナータスク (常に起動)
-    let event_tx_clone_crossterm = event_tx.clone();
-    tokio::spawn(async move {
-        loop {
-            if event::poll(Duration::from_millis(50)).unwrap() {
-                if let Ok(crossterm_event) = event::read() {
-                    if event_tx_clone_crossterm.send(AppEvent::Crossterm(crossterm_event)).await.is_err() {
-                        break;
-                    }
+    // 1. Crosstermイベントリスナータスク (--headless 以外で起動。headless ではシグナルか max_calls で終了する)
+    // event::poll はスレッドをブロックするため、専用のスレッドで動かす (ワーカースレッドが1つの環境で他のタスクを止めないように)
+    if !headless {
+        let event_tx_clone_crossterm = event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            // メインループが終了したら (受信側が閉じたら) 抜ける
+            while !event_tx_clone_crossterm.is_closed() {
+                // マウスの移動は使わないため送らない (移動のたびに再描画しないように)
+                if event::poll(Duration::from_millis(50)).unwrap()
+                    && let Ok(crossterm_event) = event::read()
+                    && !matches!(crossterm_event, CrosstermEvent::Mouse(mouse) if matches!(mouse.kind, MouseEventKind::Moved | MouseEventKind::Drag(_)))
+                    && event_tx_clone_crossterm.blocking_send(AppEvent::Crossterm(crossterm_event)).is_err()
+                {
+                    break;
                 }
             }
-        }
-    });
+        });
+    }
 
     // 2. タイマー更新タスク (APIエンドポイントとディレクトリが設定されている場合のみ、実質的に機能する)
+    // 画面の更新 (UI_TICK_INTERVAL ごと) と実行の判定 (check_interval_ms ごと) は別の間隔で行う
     let app_clone_tick = Arc::clone(&app);
     let event_tx_clone_tick = event_tx.clone();
     tokio::spawn(async move {
+        let mut ui_tick = interval(UI_TICK_INTERVAL);
+        ui_tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut check_interval_ms = app_clone_tick.lock().unwrap().config.check_interval_ms;
+        let mut check_tick = check_timer(check_interval_ms);
+        let mut last_check = std::time::Instant::now();
+        let mut last_debug_log = None;
         loop {
-            sleep(Duration::from_secs(1)).await;
+            tokio::select! {
+                _ = ui_tick.tick() => {}
+                _ = check_tick.tick() => {
+                    check_api_trigger(&app_clone_tick, &event_tx_clone_tick, &mut last_check, &mut last_debug_log).await;
+                    // 設定の再読み込みで間隔が変わった場合は、次の判定から新しい間隔にする
+                    let configured_ms = app_clone_tick.lock().unwrap().config.check_interval_ms;
+                    if configured_ms != check_interval_ms {
+                        check_interval_ms = configured_ms;
+                        check_tick = check_timer(check_interval_ms);
+                    }
+                    continue;
+                }
+            }
 
-            let api_trigger_params: Option<(String, bool, Option<PathBuf>)> = {
+            let clock_state = {
                 let mut app_guard = app_clone_tick.lock().unwrap();
                 app_guard.update_time(); // 時間は常に更新
-
-                let mut params: Option<(String, bool, Option<PathBuf>)> = None;
-
-                // APIエンドポイントが空でない、かつJSON保存ディレクトリが設定されている場合のみトリガー判定を行う
-                if !app_guard.api_endpoint.is_empty() && app_guard.today_json_dir.is_some() {
-                    // is_first_api_callがtrueの場合はタイマーによるAPI呼び出しは行わない
-                    // 初回API呼び出しは起動時にAppEvent::ApiCallTriggeredで処理されるため
-                    if app_guard.is_first_api_call {
-                        // 何もしない
-                    } else if app_guard.mode == AppMode::OnTimeMode {
-                        if let Some(next_trigger) = app_guard.next_trigger_time {
-                            let now = chrono::Local::now().naive_local();
-                            if now >= next_trigger {
-                                app_guard.set_status_message(format!("定刻モード: {}にAPI実行をトリガーします。", next_trigger.format("%H:%M:%S")));
-                                params = Some((
-                                    app_guard.api_endpoint.clone(),
-                                    false, // タイマーからの呼び出しは常に初回ではない
-                                    app_guard.today_json_dir.clone(),
-                                ));
-                                app_guard.set_next_trigger_time();
-                            }
-                        }
-                    } else if app_guard.mode == AppMode::ClockMode {
-                        app_guard.decrement_timer();
-                        if app_guard.remaining_duration.num_seconds() <= 0 {
-                            app_guard.set_status_message("クロックモード: タイマーが0になりました。API実行をトリガーします。".to_string());
-                            params = Some((
-                                app_guard.api_endpoint.clone(),
-                                false, // タイマーからの呼び出しは常に初回ではない
-                                app_guard.today_json_dir.clone(),
-                            ));
-                            app_guard.reset_timer();
-                        }
-                    }
-                }
-                params
+                app_guard.clock_state()
             };
 
-            // ここで直接 http::fetch_api_data を呼び出す代わりに、イベントを送信する
-            if let Some((endpoint, is_first_call, json_dir)) = api_trigger_params {
-                if event_tx_clone_tick.send(AppEvent::ApiCallTriggered {
-                    endpoint,
-                    is_first_call,
-                    json_dir,
-                }).await.is_err() {
-                    eprintln!("Failed to send API call trigger from timer task.");
-                }
+            // クロックモードでは残り時間を毎秒保存し、再起動後に続きから数えられるようにする (ロックを解放してから書き出す)
+            if let Some((state_path, state)) = clock_state
+                && let Err(e) = state.save(&state_path).await
+            {
+                app_clone_tick.lock().unwrap().add_debug_log(format!("残り時間の保存に失敗しました: {}", e));
             }
 
             // このTickイベントは毎秒UIを更新する目的で継続
@@ -229,17 +679,60 @@ async fn main() -> io::Result<()> {
         }
     });
 
-    // 3. APIアクセスワーカータスク（このタスクは不要だが、以前の構造に合わせて残す）
-    let _ = tokio::spawn(async move {});
+    // 3. ハートビートタスク (heartbeat_secs ごとに統計を1行 stderr に出力する)
+    // TUI の描画を崩さないよう、stderr がリダイレクトされている場合 (または --headless の場合) のみ出力する
+    let heartbeat_secs = app.lock().unwrap().config.heartbeat_secs;
+    if heartbeat_secs > 0 && (headless || !io::stderr().is_terminal()) {
+        let app_clone_heartbeat = Arc::clone(&app);
+        tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_secs(heartbeat_secs)).await;
+                let line = app_clone_heartbeat.lock().unwrap().heartbeat_line(chrono::Local::now().naive_local());
+                eprintln!("{}", line);
+            }
+        });
+    }
 
+    // 4. メトリクスサーバー (metrics_port が設定されている場合のみ。既定では同じホストからしか取得できない)
+    let (metrics_port, metrics_bind) = {
+        let app_guard = app.lock().unwrap();
+        (app_guard.config.metrics_port, app_guard.config.metrics_bind)
+    };
+    if let Some(port) = metrics_port {
+        let address = std::net::SocketAddr::new(metrics_bind, port);
+        match tokio::net::TcpListener::bind(address).await {
+            Ok(listener) => {
+                app.lock().unwrap().set_status_message(format!("メトリクスを http://{}/metrics で公開します。", address));
+                tokio::spawn(metrics::serve(listener, Arc::clone(&app)));
+            }
+            Err(e) => app.lock().unwrap().set_error(format!("メトリクスサーバーを {} で起動できませんでした: {}", address, e)),
+        }
+    }
 
-    // 4. メインアプリケーションループ (UI描画とイベント処理)
+    // 5. シグナルの監視 (systemd や Docker からの停止でもターミナルを元に戻してから終了する)
+    let event_tx_clone_signal = event_tx.clone();
+    let app_clone_signal = Arc::clone(&app);
+    tokio::spawn(async move {
+        match wait_for_shutdown_signal().await {
+            Ok(name) => {
+                let _ = event_tx_clone_signal.send(AppEvent::ShutdownSignal(name)).await;
+            }
+            Err(e) => app_clone_signal.lock().unwrap().add_log_with_level(LogLevel::Warn, format!("シグナルの監視を開始できませんでした: {}", e)),
+        }
+    });
+
+    // 実行中のAPI呼び出し (終了時に完了を少しだけ待つ)
+    let mut in_flight_calls = JoinSet::new();
+
+    // 6. メインアプリケーションループ (UI描画とイベント処理)
     loop {
-        // UI描画
-        terminal.draw(|frame| {
-            let mut app_guard = app.lock().unwrap();
-            ui::ui(frame, &mut *app_guard);
-        })?;
+        // UI描画 (--headless では描画しない)
+        if let Some(terminal) = terminal.as_mut() {
+            terminal.draw(|frame| {
+                let mut app_guard = app.lock().unwrap();
+                ui::ui(frame, &mut app_guard);
+            })?;
+        }
 
         // イベント処理
         if let Some(event) = event_rx.recv().await {
@@ -248,40 +741,141 @@ async fn main() -> io::Result<()> {
 
             match event {
                 AppEvent::Crossterm(crossterm_event) => {
-                    let log_area_height = terminal.size()?.height;
-                    current_app.handle_event(&crossterm_event, log_area_height);
-                    if let CrosstermEvent::Key(key) = crossterm_event {
-                        match key.code {
-                            KeyCode::Char('q') => {
-                                current_app.running = false;
+                    // 'q' による終了も含め、キー入力は App 側で処理する (編集フォーム入力中は終了しない)
+                    // ロック中のため、待機せずにイベントキューへ送る
+                    match current_app.handle_event(&crossterm_event) {
+                        Some(AppAction::RetryFailed(request)) => {
+                            let retry_event = AppEvent::ApiCallTriggered {
+                                json_dir: current_app.json_dir_for(&request.endpoint),
+                                endpoint: request.endpoint,
+                                is_first_call: request.is_first_call,
+                            };
+                            if event_tx.try_send(retry_event).is_err() {
+                                current_app.set_error("再試行のトリガーの送信に失敗しました。".to_string());
+                            }
+                        }
+                        Some(AppAction::TriggerNow(targets)) => {
+                            for (endpoint, json_dir) in targets {
+                                if event_tx.try_send(AppEvent::ApiCallTriggered {
+                                    endpoint,
+                                    is_first_call: false, // 手動の呼び出しはレスポンスを保存する
+                                    json_dir,
+                                }).is_err() {
+                                    current_app.set_error("手動トリガーの送信に失敗しました。".to_string());
+                                    break;
+                                }
                             }
-                            _ => {} // モード切り替えキーは削除済み
                         }
+                        Some(AppAction::ReloadConfig) => {
+                            // 読み込みかクライアントの作成に失敗した場合は、以前の設定のまま動作を続ける
+                            match Config::load_from_file(&config_path).and_then(|cfg| http::build_client(&cfg).map(|client| (cfg, client))) {
+                                Ok((cfg, client)) => {
+                                    http_client = Some(client);
+                                    if let Some(message) = cfg.ip_version.forced_message() {
+                                        current_app.add_log(message.to_string());
+                                    }
+                                    let endpoints = cfg.api.as_slice().to_vec();
+                                    // 複数のエンドポイントに変わった場合は、エンドポイントごとのディレクトリを用意し直す
+                                    if current_app.apply_config(cfg) && endpoints.len() > 1
+                                        && let Some(today_json_dir) = current_app.today_json_dir.clone()
+                                    {
+                                        let app_clone_for_dirs = Arc::clone(&app);
+                                        tokio::spawn(async move {
+                                            let result = DataManager::setup_endpoint_dirs(&today_json_dir, &endpoints).await;
+                                            let mut app_guard = app_clone_for_dirs.lock().unwrap();
+                                            match result {
+                                                // 待っている間にさらに再読み込みされた場合は適用しない
+                                                Ok(endpoint_dirs) if app_guard.api_endpoints == endpoints => app_guard.endpoint_json_dirs = endpoint_dirs,
+                                                Ok(_) => {}
+                                                Err(e) => app_guard.set_error(format!("エンドポイントごとのディレクトリの作成に失敗しました: {}", e)),
+                                            }
+                                        });
+                                    }
+                                }
+                                Err(e) => current_app.set_error(format!("設定ファイルの再読み込みに失敗しました (以前の設定で動作を続けます): {}", e)),
+                            }
+                        }
+                        None => {}
                     }
                 }
                 AppEvent::Tick => {
                     // 何もしない
                 }
+                AppEvent::ShutdownSignal(name) => {
+                    current_app.set_status_message(format!("{} を受信したため終了します", name));
+                    current_app.running = false;
+                }
                 // AppEvent::ApiCallTriggered イベントのハンドラーを一本化
                 AppEvent::ApiCallTriggered { endpoint, is_first_call, json_dir } => {
+                    // クライアントを作成できなかった場合はデータディレクトリも準備されないため、ここには来ない
+                    let Some(client) = http_client.clone() else {
+                        continue;
+                    };
                     // API呼び出しがトリガーされたら、実際にAPIを呼び出すタスクを起動
                     let app_clone_for_http = Arc::clone(&app);
                     let api_tx_clone_for_http = event_tx.clone();
+                    let config = Arc::clone(&current_app.config);
 
-                    tokio::spawn(async move {
-                        let result_msg = http::fetch_api_data(
+                    in_flight_calls.spawn(async move {
+                        let (outcome, metrics) = http::fetch_api_data(
+                            client,
                             is_first_call, // イベントから受け取ったフラグをそのまま渡す
-                            endpoint,
+                            endpoint.clone(),
                             json_dir,
                             app_clone_for_http,
+                            config,
                         ).await;
-                        if api_tx_clone_for_http.send(AppEvent::ApiCallCompleted(result_msg)).await.is_err() {
+                        if api_tx_clone_for_http.send(AppEvent::ApiCallCompleted { endpoint, is_first_call, outcome, metrics }).await.is_err() {
                             eprintln!("Failed to send API call result from http module.");
                         }
                     });
                 }
-                AppEvent::ApiCallCompleted(msg) => {
-                    current_app.add_log(format!("{}", msg));
+                AppEvent::ApiCallCompleted { endpoint, is_first_call, outcome, metrics } => {
+                    // 完了したタスクを JoinSet から取り除く
+                    while in_flight_calls.try_join_next().is_some() {}
+                    // 呼び出し回数はモードを切り替えても引き継ぎ、再起動したときだけ0に戻る
+                    if !outcome.is_skipped() {
+                        current_app.record_call_result(outcome.is_success());
+                        if let Some(status) = outcome.status() {
+                            current_app.record_status(status);
+                        }
+                    }
+                    let mut message = outcome_log_message(is_first_call, &outcome, &current_app.config);
+                    if !outcome.is_skipped() {
+                        message.push_str(&metrics_log_suffix(&metrics));
+                    }
+                    current_app.add_call_history(outcome_summary(&outcome));
+                    // 送信しなかった呼び出し (ドライラン・レート制限) はログにだけ記録する
+                    if headless && !outcome.is_skipped() {
+                        println!("{}", headless_output_line(&chrono::Local::now().to_rfc3339(), &endpoint, &outcome));
+                    }
+                    let failed = !outcome.is_success() && !outcome.is_skipped();
+                    if failed && current_app.should_notify_error(std::time::Instant::now()) {
+                        notify_error(Arc::clone(&app), message.clone());
+                    }
+                    let level = if failed {
+                        LogLevel::Error
+                    } else if outcome.is_warning() {
+                        LogLevel::Warn
+                    } else {
+                        LogLevel::Info
+                    };
+                    if matches!(outcome, ApiCallOutcome::SaveError { .. }) {
+                        // 保存の失敗 (ディスクの空き不足や権限など) は見落とさないよう、ステータス欄にも赤色で表示する
+                        current_app.set_error(message);
+                    } else {
+                        current_app.add_log_with_level(level, message);
+                    }
+                    // 送信しなかった呼び出し (ドライラン・レート制限) は通知しない
+                    if !outcome.is_skipped()
+                        && let (Some(client), Some(url)) = (webhook_client.clone(), current_app.config.webhook_url.clone())
+                    {
+                        send_webhook(Arc::clone(&app), client, url, endpoint, outcome.status());
+                    }
+                    // 指定回数に達した場合はここで running が false になり、ループを抜ける
+                    if (!is_first_call || current_app.config.save_first_response) && !outcome.is_skipped() {
+                        current_app.record_completed_fetch();
+                    }
                 }
             }
 
@@ -292,9 +886,108 @@ async fn main() -> io::Result<()> {
     }
 
     // ターミナルをクリーンアップ
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+    if let Some(mut terminal) = terminal {
+        disable_raw_mode()?;
+        if !no_alt_screen {
+            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        }
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+        terminal.show_cursor()?;
+    }
+
+    // 実行中のAPI呼び出しは猶予の間だけ完了を待ち、終わらなければ中断する (保存途中のファイルを残しにくくする)
+    if !in_flight_calls.is_empty() {
+        eprintln!("実行中のAPI呼び出しの完了を待っています (最大{}秒)...", SHUTDOWN_GRACE_PERIOD.as_secs());
+        if tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, async { while in_flight_calls.join_next().await.is_some() {} }).await.is_err() {
+            eprintln!("API呼び出しが完了しなかったため中断します。");
+            in_flight_calls.shutdown().await;
+        }
+    }
+
+    // クロックモードの残り時間を保存する
+    let clock_state = app.lock().unwrap().clock_state();
+    if let Some((state_path, state)) = clock_state
+        && let Err(e) = state.save(&state_path).await
+    {
+        eprintln!("残り時間の保存に失敗しました: {}", e);
+    }
+
+    // 呼び出しの累計を保存する
+    let stats = app.lock().unwrap().stats_path.clone().map(|path| (path, app.lock().unwrap().saved_stats()));
+    if let Some((stats_path, stats)) = stats
+        && let Err(e) = stats.save(&stats_path).await
+    {
+        eprintln!("統計ファイル '{}' の保存に失敗しました: {}", stats_path.display(), e);
+    }
+
+    // 終了時のサマリーを表示 (--headless では stdout を結果の出力だけにするため stderr に出す)
+    let app_guard = app.lock().unwrap();
+    if headless {
+        eprintln!("{}", app_guard.exit_summary());
+    } else {
+        println!("{}", app_guard.exit_summary());
+    }
+
+    Ok(ExitCode::from(app_guard.exit_code()))
+}
 
-    Ok(())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn panic_hook_runs_restore_for_child_thread_panic() {
+        let restored = Arc::new(AtomicBool::new(false));
+        let restored_in_hook = Arc::clone(&restored);
+        install_panic_hook_with(move || restored_in_hook.store(true, Ordering::SeqCst));
+
+        let result = std::thread::spawn(|| panic!("テスト用のパニック")).join();
+        // 他のテストに影響しないよう既定のフックに戻す
+        let _ = std::panic::take_hook();
+
+        assert!(result.is_err());
+        assert!(restored.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn no_alt_screen_flag_does_not_change_config_path() {
+        let args = || ["--no-alt-screen", "profiles/staging.json"].into_iter().map(String::from);
+        assert!(has_flag(args(), "--no-alt-screen"));
+        assert_eq!(config_path_from_args(args()), "profiles/staging.json");
+        assert!(!has_flag(std::iter::empty(), "--no-alt-screen"));
+    }
+
+    #[tokio::test]
+    async fn scheduled_trigger_runs_after_failed_first_call() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(500)).mount(&server).await;
+        let endpoint = format!("{}/items", server.uri());
+        let config = Arc::new(Config { max_retries: 0, ..Config::default() });
+        let app = Arc::new(Mutex::new(App::new(AppMode::ClockMode, 0, 0, 1, vec![endpoint.clone()], Arc::clone(&config))));
+        app.lock().unwrap().today_json_dir = Some(PathBuf::from("jsons"));
+
+        let (outcome, _) = http::fetch_api_data(reqwest::Client::new(), true, endpoint.clone(), None, Arc::clone(&app), config).await;
+        assert!(matches!(outcome, ApiCallOutcome::HttpError { status: 500 }));
+
+        app.lock().unwrap().remaining_duration = chrono::Duration::zero();
+        let (event_tx, mut event_rx) = mpsc::channel(10);
+        check_api_trigger(&app, &event_tx, &mut std::time::Instant::now(), &mut None).await;
+        assert!(matches!(
+            event_rx.try_recv(),
+            Ok(AppEvent::ApiCallTriggered { endpoint: triggered, is_first_call: false, .. }) if triggered == endpoint
+        ));
+    }
+
+    #[test]
+    fn headless_output_line_is_tab_separated() {
+        let line = headless_output_line("2024-05-01T09:00:00+09:00", "https://api.example.com/items", &ApiCallOutcome::NotModified);
+        assert_eq!(line, "2024-05-01T09:00:00+09:00\thttps://api.example.com/items\t304 (変更なし)\t-");
+
+        let outcome = ApiCallOutcome::NetworkError { msg: "接続できません".to_string() };
+        assert_eq!(headless_output_line("t", "e", &outcome).split('\t').collect::<Vec<_>>(), ["t", "e", "エラー: 接続できません", "-"]);
+    }
+}
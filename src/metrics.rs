@@ -0,0 +1,82 @@
+// src/metrics.rs
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::app::App;
+
+/// Prometheus のテキスト形式でメトリクスを返すHTTPサーバーを起動する
+/// GET /metrics 以外のリクエストには 404 を返す
+pub async fn serve(listener: TcpListener, app: Arc<Mutex<App>>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let app = Arc::clone(&app);
+        tokio::spawn(async move {
+            // 接続ごとのエラー (途中で切断されたなど) はスクレイプ側で再試行されるため無視する
+            let _ = handle_connection(stream, app).await;
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, app: Arc<Mutex<App>>) -> std::io::Result<()> {
+    // リクエスト行だけを見ればよいため、最初の読み込み分だけを使う
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let is_metrics = request.lines().next()
+        .is_some_and(|line| line.starts_with("GET /metrics ") || line.starts_with("GET /metrics?"));
+
+    let response = if is_metrics {
+        let body = render(&app.lock().unwrap());
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// 起動してからの呼び出し回数と最後の呼び出し時刻 (UNIX時間、まだ呼び出していなければ 0)
+/// stats_file を設定した場合、回数は以前の実行からの累計になる
+fn render(app: &App) -> String {
+    let last_call_timestamp = app.last_call_at.map_or(0, |last_call_at| last_call_at.timestamp());
+    format!(
+        "# HELP budilnik_calls_total 起動してからのAPI呼び出しの回数 (stats_file を設定した場合は以前の実行からの累計)\n\
+         # TYPE budilnik_calls_total counter\n\
+         budilnik_calls_total {}\n\
+         # HELP budilnik_calls_failed_total 起動してから失敗したAPI呼び出しの回数 (stats_file を設定した場合は以前の実行からの累計)\n\
+         # TYPE budilnik_calls_failed_total counter\n\
+         budilnik_calls_failed_total {}\n\
+         # HELP budilnik_last_call_timestamp 最後にAPIを呼び出した時刻 (UNIX時間)\n\
+         # TYPE budilnik_last_call_timestamp gauge\n\
+         budilnik_last_call_timestamp {}\n",
+        app.calls_total, app.calls_failed, last_call_timestamp
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppMode;
+    use crate::config::Config;
+
+    #[test]
+    fn render_reports_counters() {
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(Config::default()));
+        assert!(render(&app).contains("budilnik_last_call_timestamp 0\n"));
+
+        app.record_call_result(true);
+        app.record_call_result(false);
+        let text = render(&app);
+        assert!(text.contains("budilnik_calls_total 2\n"));
+        assert!(text.contains("budilnik_calls_failed_total 1\n"));
+        assert!(!text.contains("budilnik_last_call_timestamp 0\n"));
+    }
+}
@@ -0,0 +1,49 @@
+// src/rate_limit.rs
+
+use std::time::{Duration, Instant};
+
+/// トークンバケット方式のレート制限
+/// 手動実行・リトライなど、すべての送信元からのリクエストで共有する
+pub struct TokenBucket {
+    capacity: f64,       // バケットの最大トークン数
+    refill_per_sec: f64, // 1秒あたりの補充トークン数
+    tokens: f64,         // 現在のトークン数
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// 満タンの状態でバケットを作成する
+    pub fn new(capacity: u32, refill_per_sec: f64) -> TokenBucket {
+        TokenBucket {
+            capacity: capacity as f64,
+            refill_per_sec,
+            tokens: capacity as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// トークンを1つ取得する
+    /// 不足している場合は、次のトークンが補充されるまでの時間を Err で返す
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec))
+        }
+    }
+
+    /// 現在のトークン数
+    pub fn tokens(&mut self) -> f64 {
+        self.refill();
+        self.tokens
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+}
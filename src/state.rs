@@ -0,0 +1,79 @@
+// src/state.rs
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// クロックモードの状態ファイル名 (データのベースディレクトリ内に置く)
+pub const CLOCK_STATE_FILENAME: &str = ".clock_state.json";
+
+/// 再起動をまたいで引き継ぐクロックモードのカウントダウン
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct ClockState {
+    pub total_seconds: i64,     // 保存時の設定間隔 (設定が変わっていたら復元しない)
+    pub remaining_seconds: i64, // 保存時の残り時間
+}
+
+impl ClockState {
+    /// 状態ファイルのパス
+    pub fn path(base_dir: &Path) -> PathBuf {
+        base_dir.join(CLOCK_STATE_FILENAME)
+    }
+
+    /// 状態ファイルを読み込む。ファイルがない場合は None を返す
+    pub async fn load(path: &Path) -> Result<Option<ClockState>> {
+        load_json(path).await
+    }
+
+    /// 状態ファイルに書き出す
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        save_json(self, path).await
+    }
+
+    /// 設定された間隔が保存時と同じ場合のみ、残り時間を返す
+    pub fn remaining_for(&self, total_seconds: i64) -> Option<i64> {
+        (self.total_seconds == total_seconds && (0..=total_seconds).contains(&self.remaining_seconds))
+            .then_some(self.remaining_seconds)
+    }
+}
+
+/// 再起動をまたいで引き継ぐ呼び出しの累計 (stats_file)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct SavedStats {
+    pub calls_total: u64,                       // 送信したAPI呼び出しの回数
+    pub calls_ok: u64,                          // 成功した回数
+    pub calls_err: u64,                         // 失敗した回数
+    pub last_success_at: Option<NaiveDateTime>, // 最後に成功した時刻
+}
+
+impl SavedStats {
+    /// 統計ファイルを読み込む。ファイルがない場合は None を返す
+    pub async fn load(path: &Path) -> Result<Option<SavedStats>> {
+        load_json(path).await
+    }
+
+    /// 統計ファイルに書き出す
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        save_json(self, path).await
+    }
+}
+
+/// JSONファイルを読み込む。ファイルがない場合は None を返す
+async fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).await?;
+    Ok(Some(serde_json::from_str(&content)?))
+}
+
+/// JSONファイルに書き出す (一時ファイルに書いてからリネームし、途中で切れたファイルを残さない)
+async fn save_json<T: Serialize>(value: &T, path: &Path) -> Result<()> {
+    let temp_path = path.with_extension("json.part");
+    fs::write(&temp_path, serde_json::to_vec(value)?).await?;
+    fs::rename(&temp_path, path).await?;
+    Ok(())
+}
@@ -1,25 +1,34 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Style},
-    text::Text,
-    widgets::{Block, Borders, Paragraph},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
     Frame,
 };
 
-use crate::app::{App, AppMode};
-use chrono::Local;
+use std::rc::Rc;
+
+use crate::app::{App, AppMode, Health, LogLevel, MAX_CALL_HISTORY, MAX_RECENT_SAVES};
+use chrono::{Local, NaiveDateTime};
 
 pub fn ui(frame: &mut Frame, app: &mut App) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints([
-            Constraint::Length(3), // 現在時刻
-            Constraint::Length(4), // API実行情報
-            Constraint::Length(3), // ステータス
-            Constraint::Min(0),    // ログ
-        ])
-        .split(frame.area());
+    let now = Local::now().naive_local();
+    let (status_text, status_color) = status_text(app);
+
+    // コンパクト表示: 現在時刻・接続状態・ステータス・API実行情報を先頭の1行にまとめ、残りをすべてログに使う
+    let chunks = main_chunks(app, frame.area());
+    if app.is_compact_ui {
+
+        let mut summary = vec![Span::raw(format!("{} ", app.current_time))];
+        summary.extend(health_spans(app, now));
+        summary.push(Span::raw(" | "));
+        summary.push(Span::styled(status_text, Style::default().fg(status_color)));
+        summary.push(Span::raw(format!(" | {}", mode_detail_text(app).replace('\n', " / "))));
+        frame.render_widget(Paragraph::new(Line::from(summary)), chunks[0]);
+
+        render_logs(frame, app, chunks[1]);
+        return;
+    }
 
     // --- 現在時刻の表示 ---
     let time_block = Block::default()
@@ -37,9 +46,149 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         .title("API実行情報")
         .borders(Borders::ALL);
 
+    let mode_detail_paragraph = Paragraph::new(Text::raw(mode_detail_text(app)))
+        .block(mode_detail_block)
+        .alignment(ratatui::layout::Alignment::Center);
+
+    frame.render_widget(mode_detail_paragraph, chunks[1]);
+
+    // --- ステータス表示 ---
+    let mut status_title = vec![Span::raw("ステータス ")];
+    status_title.extend(health_spans(app, now));
+    let status_block = Block::default()
+        .title(Line::from(status_title))
+        .borders(Borders::ALL);
+
+    let status_paragraph = Paragraph::new(Text::raw(status_text))
+        .block(status_block)
+        .alignment(ratatui::layout::Alignment::Center)
+        .style(Style::default().fg(status_color));
+
+    frame.render_widget(status_paragraph, chunks[2]);
+
+    // --- 最近の結果の表示 (時刻とステータスコードまたはエラー) ---
+    let history_block = Block::default()
+        .title("最近の結果")
+        .borders(Borders::ALL);
+
+    let history_text = if app.call_history.is_empty() {
+        "まだAPIを呼び出していません".to_string()
+    } else {
+        app.call_history.iter()
+            .rev() // 新しいものを上に表示
+            .map(|(ts, outcome)| format!("{}  {}", ts, outcome))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    let history_paragraph = Paragraph::new(Text::raw(history_text))
+        .block(history_block)
+        .alignment(ratatui::layout::Alignment::Left);
+
+    // --- ステータス集計と今後の実行予定の表示 (最近の結果の右側) ---
+    let (history_area, status_area, upcoming_area) = history_areas(app, chunks[3]);
+    frame.render_widget(history_paragraph, history_area);
+    let status_summary = app.status_summary();
+    let status_summary_text = if status_summary.is_empty() {
+        "-".to_string()
+    } else {
+        status_summary.iter()
+            .map(|(status, count)| format!("{}: {}", status, count))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    let status_summary_paragraph = Paragraph::new(Text::raw(status_summary_text))
+        .block(Block::default().title("ステータス").borders(Borders::ALL))
+        .alignment(ratatui::layout::Alignment::Left);
+    frame.render_widget(status_summary_paragraph, status_area);
+    if let Some(upcoming_area) = upcoming_area {
+        let upcoming_text = app.upcoming_runs(now, app.config.upcoming_runs).into_iter()
+            .map(|run| next_run_label(run, now))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let upcoming_paragraph = Paragraph::new(Text::raw(upcoming_text))
+            .block(Block::default().title("今後の実行").borders(Borders::ALL))
+            .alignment(ratatui::layout::Alignment::Center);
+        frame.render_widget(upcoming_paragraph, upcoming_area);
+    }
+
+    // --- ログ表示 ---
+    render_logs(frame, app, chunks[4]);
+}
+
+// 画面全体を上部のパネルとログの領域 (最後の要素) に分割する
+fn main_chunks(app: &App, area: Rect) -> Rc<[Rect]> {
+    // コンパクト表示では上部の情報を1行にまとめる
+    if app.is_compact_ui {
+        return Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // 現在時刻・接続状態・ステータス・API実行情報
+                Constraint::Min(0),    // ログ
+            ])
+            .split(area);
+    }
+    Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3), // 現在時刻
+            Constraint::Length(5), // API実行情報
+            Constraint::Length(3), // ステータス
+            Constraint::Length(MAX_CALL_HISTORY as u16 + 2), // 最近の結果 (ボーダー分を含む)
+            Constraint::Min(0),    // ログ
+        ])
+        .split(area)
+}
+
+// 最近の結果の領域を、結果の一覧・ステータス集計・今後の実行予定 (upcoming_runs が 0 なら表示しない) に分割する
+fn history_areas(app: &App, area: Rect) -> (Rect, Rect, Option<Rect>) {
+    let upcoming_width = if app.config.upcoming_runs == 0 { 0 } else { 22 };
+    let history_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(0),                 // 最近の結果
+            Constraint::Length(16),             // ステータス集計 ("503: 1234567" とボーダー分)
+            Constraint::Length(upcoming_width), // 今後の実行 ("MM/DD HH:MM:SS" とボーダー分)
+        ])
+        .split(area);
+    (history_chunks[0], history_chunks[1], (upcoming_width > 0).then_some(history_chunks[2]))
+}
+
+// ログの領域を保存ファイル一覧ペイン ('l' キーで表示している場合のみ) とログ本体に分割する
+fn log_pane_areas(app: &App, log_area: Rect) -> (Option<Rect>, Rect) {
+    if !app.show_recent_saves {
+        return (None, log_area);
+    }
+    let pane_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(MAX_RECENT_SAVES as u16 + 2), // 保存ファイル一覧 (ボーダー分を含む)
+            Constraint::Min(0),                              // ログ
+        ])
+        .split(log_area);
+    (Some(pane_chunks[0]), pane_chunks[1])
+}
+
+// 画面の大きさが area のときにログ本体に表示できる行数 (枠線の分を除く)
+// 端末のサイズが変わったとき、次の描画を待たずにスクロール位置を調整するために使う
+pub fn log_view_height(app: &App, area: Rect) -> usize {
+    let chunks = main_chunks(app, area);
+    let (_, log_area) = log_pane_areas(app, chunks[chunks.len() - 1]);
+    log_area.height.saturating_sub(2) as usize
+}
+
+// API実行情報パネルの内容 (モードごとの設定と次の実行までの時間、呼び出し回数)
+fn mode_detail_text(app: &App) -> String {
     let mode_detail_text = match app.mode {
         AppMode::OnTimeMode => {
-            let initial_time_str = format!("{:02}時{:02}分{:02}秒", app.initial_h, app.initial_m, app.initial_s);
+            let initial_time_str = if let Some(cron) = &app.config.cron {
+                format!("cron: {}", cron)
+            } else if app.trigger_times.is_empty() {
+                format!("{:02}時{:02}分{:02}秒", app.initial_h, app.initial_m, app.initial_s)
+            } else {
+                app.trigger_times.iter().map(|time| time.format("%H:%M:%S").to_string()).collect::<Vec<_>>().join(", ")
+            };
             let mut next_execution_str = "計算中...".to_string();
 
             if let Some(next_trigger) = app.next_trigger_time {
@@ -50,7 +199,7 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                     let h = total_seconds / 3600;
                     let m = (total_seconds % 3600) / 60;
                     let s = total_seconds % 60;
-                    next_execution_str = format!("あと{:02}時間{:02}分{:02}秒", h, m, s);
+                    next_execution_str = format!("あと{:02}時間{:02}分{:02}秒 (次回: {})", h, m, s, next_run_label(next_trigger, now));
                 } else {
                     next_execution_str = "実行時刻を過ぎました".to_string();
                 }
@@ -69,106 +218,251 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
             let remaining_m = (effective_remaining_seconds % 3600) / 60;
             let remaining_s = effective_remaining_seconds % 60;
 
+            // 次に実行する時刻 (現在時刻 + 残り時間)
+            let now = Local::now().naive_local();
+            let next_run = now + chrono::Duration::seconds(effective_remaining_seconds);
+
             format!(
-                "設定周期: {:02}時間{:02}分{:02}秒\n次の実行まで: {:02}時間{:02}分{:02}秒",
+                "設定周期: {:02}時間{:02}分{:02}秒\n次の実行まで: {:02}時間{:02}分{:02}秒 (次回: {})",
                 total_h, total_m, total_s,
-                remaining_h, remaining_m, remaining_s
+                remaining_h, remaining_m, remaining_s,
+                next_run_label(next_run, now)
             )
         }
     };
 
-    let mode_detail_paragraph = Paragraph::new(Text::raw(mode_detail_text))
-        .block(mode_detail_block)
-        .alignment(ratatui::layout::Alignment::Center);
-
-    frame.render_widget(mode_detail_paragraph, chunks[1]);
-
-    // --- ステータス表示 ---
-    let status_chunk_index = 2;
-    let status_block = Block::default()
-        .title("ステータス")
-        .borders(Borders::ALL);
+    // 起動してからの呼び出し回数 (stats_file を設定した場合は以前の実行からの累計)
+    format!(
+        "{}\n呼び出し{}: 合計 {} / 成功 {} / 失敗 {}",
+        mode_detail_text,
+        if app.stats_path.is_some() { " (累計)" } else { "" },
+        app.calls_total, app.calls_ok, app.calls_failed
+    )
+}
 
-    let status_paragraph = if let Some(msg) = &app.error_message {
-        Paragraph::new(Text::raw(msg))
-            .block(status_block)
-            .alignment(ratatui::layout::Alignment::Center)
-            .style(Style::default().fg(Color::Red))
+// ステータス欄に表示する文字列と色 (確認・入力中の表示を優先する)
+fn status_text(app: &App) -> (String, Color) {
+    if app.is_quit_confirming {
+        ("終了しますか？ (y/n)".to_string(), Color::Cyan)
+    } else if let Some(input) = &app.time_input {
+        let prompt = match app.mode {
+            AppMode::OnTimeMode => "新しい実行時刻",
+            AppMode::ClockMode => "新しい実行間隔",
+        };
+        (format!("{} (時:分:秒): {}_  [Enter: 適用 / Esc: 取消]", prompt, input), Color::Cyan)
+    } else if app.paused {
+        ("一時停止中  [p: 再開]".to_string(), Color::Magenta)
+    } else if let Some(remaining) = app.startup_countdown {
+        (format!("初回のAPI呼び出しまで あと{}秒", remaining), Color::Cyan)
+    } else if let Some(msg) = &app.error_message {
+        (msg.clone(), Color::Red)
     } else if let Some(msg) = &app.status_message {
-        Paragraph::new(Text::raw(msg))
-            .block(status_block)
-            .alignment(ratatui::layout::Alignment::Center)
-            .style(Style::default().fg(Color::Yellow))
+        (msg.clone(), Color::Yellow)
     } else {
-        Paragraph::new(Text::raw("待機中..."))
-            .block(status_block)
-            .alignment(ratatui::layout::Alignment::Center)
-            .style(Style::default().fg(Color::DarkGray))
+        ("待機中...".to_string(), Color::DarkGray)
+    }
+}
+
+// 接続状態の色付き表示 (緑: 正常、黄: 実行予定を過ぎても成功していない、赤: 直近の呼び出しが失敗) と最後に成功してからの経過時間
+fn health_spans(app: &App, now: NaiveDateTime) -> Vec<Span<'static>> {
+    let (health_label, health_color) = match app.health(now) {
+        Health::Unknown => ("● 未実行", Color::DarkGray),
+        Health::Healthy => ("● 正常", Color::Green),
+        Health::Stale => ("● 遅延", Color::Yellow),
+        Health::Failing => ("● 失敗", Color::Red),
     };
+    let mut spans = vec![Span::styled(health_label, Style::default().fg(health_color))];
+    if let Some(last_success_at) = app.last_success_at {
+        spans.push(Span::raw(format!(" (最終成功: {}) ", elapsed_label(now.signed_duration_since(last_success_at)))));
+    }
+    spans
+}
 
-    frame.render_widget(status_paragraph, chunks[status_chunk_index]);
+// ログ (と 'l' キーで表示する保存ファイル一覧ペイン) を area に描画する
+fn render_logs(frame: &mut Frame, app: &mut App, area: Rect) {
+    let (saves_area, log_area) = log_pane_areas(app, area);
 
-    // --- ログ表示 ---
-    let log_chunk_index = 3;
-    if chunks.len() > log_chunk_index {
-        let log_area = chunks[log_chunk_index];
-        let log_content_area = log_area; // スクロールバーがないため、ログ本体がログエリア全体を使用
-
-        // ログ表示領域の実際の高さを取得（ボーダー分を引く）
-        let display_height = log_content_area.height.saturating_sub(2) as usize;
-        // ログ全体の行数
-        let total_log_lines = app.logs.len();
-
-        // スクロール可能な最大位置
-        let max_scroll_position = total_log_lines.saturating_sub(display_height).max(0);
-
-        // app.log_scroll の値を適切に調整し、常に有効な範囲に保つ
-        if app.is_log_auto_scroll {
-            app.log_scroll = max_scroll_position;
-        } else {
-            app.log_scroll = app.log_scroll.min(max_scroll_position).max(0);
-        }
+    // --- 保存ファイル一覧ペイン ('l' キーで表示切り替え) ---
+    if let Some(saves_area) = saves_area {
+        let saves_block = Block::default()
+            .title("最近保存したファイル")
+            .borders(Borders::ALL);
 
-        // ページ計算
-        let current_page = if display_height == 0 { // 表示可能な行がない場合
-            0
+        let saves_text = if app.recent_saves.is_empty() {
+            "まだ保存されたファイルはありません".to_string()
         } else {
-            // 現在のスクロール位置 / 1ページあたりの行数 + 1
-            // ログが0行の場合も1ページ目として扱う
-            (app.log_scroll / display_height) + 1
+            app.recent_saves.iter()
+                .rev() // 新しいものを上に表示
+                .map(|record| format!("{} {} ({} bytes, ステータス {})", record.ts, record.filename, record.bytes, record.status))
+                .collect::<Vec<String>>()
+                .join("\n")
         };
 
-        let total_pages = if display_height == 0 { // 表示可能な行がない場合
-            0
-        } else {
-            // (ログ総行数 + 1ページあたりの行数 - 1) / 1ページあたりの行数
-            // 例えば、10行表示でログが11行なら2ページ
-            (total_log_lines + display_height - 1) / display_height
-        };
+        let saves_paragraph = Paragraph::new(Text::raw(saves_text))
+            .block(saves_block)
+            .alignment(ratatui::layout::Alignment::Left);
 
-        // ログブロックのタイトルにページ情報を追加
-        let log_title = format!("ログ ({}/{})", current_page, total_pages);
-        let log_block = Block::default()
-            .title(log_title)
-            .borders(Borders::ALL);
+        frame.render_widget(saves_paragraph, saves_area);
+    }
+
+    let log_content_area = log_area; // スクロールバーは右側の枠線の上に描くため、ログ本体がログエリア全体を使用
 
-        // 表示するログの範囲を決定
-        let start_index = app.log_scroll;
-        let end_index = (start_index + display_height).min(total_log_lines);
+    // ログ表示領域の実際の高さを取得（ボーダー分を引く）
+    let display_height = log_content_area.height.saturating_sub(2) as usize;
+    // キー操作 (PageUp/PageDown など) のスクロール量に使うため記録し、app.log_scroll を有効な範囲に保つ
+    app.set_log_view_height(display_height);
+    // 表示対象のログ全体の行数 (絞り込み中は一致した行数)
+    let total_log_lines = app.visible_logs().len();
 
-        let visible_logs: Vec<String> = app.logs.iter()
-            .skip(start_index)
-            .take(end_index.saturating_sub(start_index))
-            .cloned()
-            .collect();
+    // スクロール可能な最大位置
+    let max_scroll_position = total_log_lines.saturating_sub(display_height);
 
-        let log_text = Text::from(visible_logs.join("\n"));
+    // ページ計算
+    // 表示可能な行がない場合は0ページとして扱う
+    // 現在のスクロール位置 / 1ページあたりの行数 + 1
+    // ログが0行の場合も1ページ目として扱う
+    let current_page = app.log_scroll.checked_div(display_height).map_or(0, |page| page + 1);
+
+    // 例えば、10行表示でログが11行なら2ページ (ログが0行でも現在のページと揃うよう1ページとする)
+    let total_pages = if display_height == 0 {
+        0
+    } else {
+        total_log_lines.div_ceil(display_height).max(1)
+    };
+
+    // ログブロックのタイトルにページ情報を追加
+    let log_title = match &app.filter_query {
+        Some(query) if app.is_filter_editing => format!("ログ ({}/{}) 絞り込み: {}_  [Enter: 確定 / Esc: 解除]", current_page, total_pages, query),
+        Some(query) => format!("ログ ({}/{}) 絞り込み: {}  [Esc: 解除]", current_page, total_pages, query),
+        None => format!("ログ ({}/{})", current_page, total_pages),
+    };
+    let log_block = Block::default()
+        .title(log_title)
+        .borders(Borders::ALL);
 
-        let log_paragraph = Paragraph::new(log_text)
-            .block(log_block)
-            .alignment(ratatui::layout::Alignment::Left)
-            .scroll((0, 0));
+    // 表示するログの範囲を決定
+    let start_index = app.log_scroll;
+    let end_index = (start_index + display_height).min(total_log_lines);
+
+    // ログのレベルごとに色分けする (エラーは赤、警告はマゼンタ、ステータスは黄、デバッグは灰色)
+    let visible_logs: Vec<Line> = app.visible_logs().into_iter()
+        .skip(start_index)
+        .take(end_index.saturating_sub(start_index))
+        .map(|record| {
+            let style = match record.level {
+                LogLevel::Error => Style::default().fg(Color::Red),
+                LogLevel::Warn => Style::default().fg(Color::Magenta),
+                LogLevel::Debug => Style::default().fg(Color::DarkGray),
+                LogLevel::Info if record.is_status => Style::default().fg(Color::Yellow),
+                LogLevel::Info => Style::default(),
+            };
+            Line::styled(record.text(), style)
+        })
+        .collect();
+
+    let log_text = Text::from(visible_logs);
+
+    let log_paragraph = Paragraph::new(log_text)
+        .block(log_block)
+        .alignment(ratatui::layout::Alignment::Left)
+        .scroll((0, 0));
+
+    frame.render_widget(log_paragraph, log_content_area);
+
+    // スクロールバー (自動スクロール中は log_scroll が末尾に追従するため、新しいログに合わせて動く)
+    let mut scrollbar_state = ScrollbarState::new(max_scroll_position + 1)
+        .position(app.log_scroll)
+        .viewport_content_length(display_height);
+    frame.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        log_content_area.inner(Margin { vertical: 1, horizontal: 0 }), // 上下の枠線の角は避ける
+        &mut scrollbar_state,
+    );
+}
+
+// 次に実行する時刻の表示 (今日なら時刻のみ、明日以降なら日付も付ける)
+fn next_run_label(next_run: NaiveDateTime, now: NaiveDateTime) -> String {
+    if next_run.date() == now.date() {
+        next_run.format("%H:%M:%S").to_string()
+    } else {
+        next_run.format("%m/%d %H:%M:%S").to_string()
+    }
+}
+
+// 経過時間の表示 (例: "30秒前", "12分前", "3時間前", "2日前")
+fn elapsed_label(elapsed: chrono::Duration) -> String {
+    let seconds = elapsed.num_seconds().max(0);
+    match seconds {
+        0..60 => format!("{}秒前", seconds),
+        60..3600 => format!("{}分前", seconds / 60),
+        3600..86400 => format!("{}時間前", seconds / 3600),
+        _ => format!("{}日前", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use ratatui::{backend::TestBackend, Terminal};
+    use std::sync::Arc;
+
+    #[test]
+    fn tiny_terminals_render_without_panicking() {
+        let config = Config { upcoming_runs: 3, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        for _ in 0..20 {
+            app.add_log("ログ".to_string());
+        }
+        for is_compact_ui in [false, true] {
+            app.is_compact_ui = is_compact_ui;
+            app.show_recent_saves = !is_compact_ui;
+            for (width, height) in [(0, 0), (1, 1), (10, 2), (40, 3), (80, 10)] {
+                let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+                terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn status_shows_startup_countdown() {
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(Config::default()));
+        app.startup_countdown = Some(3);
+        assert_eq!(status_text(&app), ("初回のAPI呼び出しまで あと3秒".to_string(), Color::Cyan));
+    }
+
+    // 空白を除いた画面の文字列 (全角文字の後ろのセルは空白になるため、空白を除いてから比べる)
+    fn screen_text(terminal: &Terminal<TestBackend>) -> String {
+        terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).filter(|symbol| *symbol != " ").collect()
+    }
+
+    #[test]
+    fn upcoming_runs_panel_is_shown_when_enabled() {
+        let config = Config { upcoming_runs: 3, ..Config::default() };
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(config));
+        let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+
+        let screen = screen_text(&terminal);
+        assert!(screen.contains("今後の実行"));
+
+        app.config = Arc::new(Config { upcoming_runs: 0, ..Config::default() });
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+        let screen = screen_text(&terminal);
+        assert!(!screen.contains("今後の実行"));
+    }
+
+    #[test]
+    fn status_panel_lists_counts_by_status() {
+        let mut app = App::new(AppMode::ClockMode, 0, 1, 0, Vec::new(), Arc::new(Config::default()));
+        for status in [200, 503, 200] {
+            app.record_status(status);
+        }
+        let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
 
-        frame.render_widget(log_paragraph, log_content_area);
+        let screen = screen_text(&terminal);
+        assert!(screen.contains("200:2"));
+        assert!(screen.contains("503:1"));
     }
-}
\ No newline at end of file
+}
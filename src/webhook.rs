@@ -0,0 +1,61 @@
+// src/webhook.rs
+
+use chrono::{DateTime, Local};
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Webhook の送信を待つ最大の時間 (APIの timeout_secs とは別に、短めに固定する)
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 呼び出しの結果として webhook_url に POST する本文
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub status: Option<u16>, // レスポンスを受け取れなかった場合は null
+    pub timestamp: String,   // RFC 3339 形式の呼び出し完了時刻
+    pub endpoint: &'a str,
+}
+
+impl<'a> WebhookPayload<'a> {
+    pub fn new(status: Option<u16>, timestamp: DateTime<Local>, endpoint: &'a str) -> Self {
+        WebhookPayload { status, timestamp: timestamp.to_rfc3339(), endpoint }
+    }
+}
+
+/// Webhook 用のHTTPクライアントを作成する
+/// APIの認証ヘッダーなどを送らないよう、API呼び出し用のクライアントとは分けておく
+pub fn build_client() -> anyhow::Result<Client> {
+    Ok(Client::builder().timeout(WEBHOOK_TIMEOUT).build()?)
+}
+
+/// 結果を POST する。2xx 以外のステータスもエラーとして返す
+/// Slack などの URL にはトークンが含まれるため、エラーメッセージには URL を含めない
+pub async fn send(client: &Client, url: &str, payload: &WebhookPayload<'_>) -> anyhow::Result<()> {
+    client.post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(payload)?)
+        .send().await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.without_url())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn payload_serializes_status_timestamp_and_endpoint() {
+        let timestamp = Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+        let payload = WebhookPayload::new(None, timestamp, "https://api.example.com/v1");
+        let value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(value["status"], serde_json::Value::Null);
+        assert_eq!(value["endpoint"], "https://api.example.com/v1");
+        assert_eq!(value["timestamp"], timestamp.to_rfc3339());
+
+        let payload = WebhookPayload::new(Some(200), timestamp, "https://api.example.com/v1");
+        assert_eq!(serde_json::to_value(&payload).unwrap()["status"], 200);
+    }
+}